@@ -0,0 +1,255 @@
+//! The command line contract of switch-to-configuration.
+//!
+//! NixOS runs `<toplevel>/bin/switch-to-configuration <action>`, and
+//! nixos-rebuild acts on its exit code. See
+//! nixos/modules/system/activation/switch-to-configuration.pl.
+
+use fstab::MountActions;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "Usage: switch-to-configuration [--toplevel PATH] [switch|boot|test|dry-activate]
+
+switch:       make the configuration the boot default and activate now
+boot:         make the configuration the boot default
+test:         activate the configuration, but don't make it the boot default
+dry-activate: show what would be done if this configuration were activated
+";
+
+/// What to do with the configuration
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Action {
+    Switch,
+    Boot,
+    Test,
+    DryActivate,
+}
+
+impl Action {
+    pub fn parse(action: &str) -> Option<Action> {
+        match action {
+            "switch" => Some(Action::Switch),
+            "boot" => Some(Action::Boot),
+            "test" => Some(Action::Test),
+            "dry-activate" => Some(Action::DryActivate),
+            _ => None,
+        }
+    }
+
+    /// Does this action make the configuration the boot default?
+    pub fn installs_boot_loader(self) -> bool {
+        self == Action::Switch || self == Action::Boot
+    }
+
+    /// Does this action change the running system?
+    pub fn activates(self) -> bool {
+        self == Action::Switch || self == Action::Test
+    }
+}
+
+/// The parsed command line
+#[derive(Debug, PartialEq, Clone)]
+pub struct Args {
+    pub action: Action,
+
+    /// `--toplevel`, overriding the toplevel found from the path of
+    /// the running executable.
+    pub toplevel: Option<PathBuf>,
+}
+
+/// Why the command line was rejected
+#[derive(Debug, PartialEq, Clone)]
+pub enum UsageError {
+    MissingAction,
+    UnknownAction(String),
+    UnexpectedArgument(String),
+    UnknownOption(String),
+    MissingToplevel,
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UsageError::MissingAction => write!(f, "no action given"),
+            UsageError::UnknownAction(ref action) => write!(f, "unknown action {:?}", action),
+            UsageError::UnexpectedArgument(ref arg) => write!(f, "unexpected argument {:?}", arg),
+            UsageError::UnknownOption(ref option) => write!(f, "unknown option {:?}", option),
+            UsageError::MissingToplevel => write!(f, "--toplevel needs a path"),
+        }
+    }
+}
+
+impl Error for UsageError {}
+
+/// Parse the arguments, not including the program name.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Args, UsageError> {
+    let mut action = None;
+    let mut toplevel = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--toplevel" {
+            toplevel = Some(PathBuf::from(args.next().ok_or(UsageError::MissingToplevel)?));
+        } else if let Some(path) = arg.strip_prefix("--toplevel=") {
+            if path.is_empty() {
+                return Err(UsageError::MissingToplevel);
+            }
+            toplevel = Some(PathBuf::from(path));
+        } else if arg.starts_with("--") {
+            return Err(UsageError::UnknownOption(arg));
+        } else if action.is_some() {
+            return Err(UsageError::UnexpectedArgument(arg));
+        } else {
+            action = Some(Action::parse(&arg).ok_or(UsageError::UnknownAction(arg))?);
+        }
+    }
+
+    Ok(Args {
+        action: action.ok_or(UsageError::MissingAction)?,
+        toplevel,
+    })
+}
+
+/// The toplevel an executable at `<toplevel>/bin/switch-to-configuration`
+/// belongs to. The path should already have its symlinks resolved,
+/// since /run/current-system is a link to the toplevel.
+pub fn toplevel_from_exe(exe: &Path) -> Option<&Path> {
+    let bin = exe.parent()?;
+    if bin.file_name()? != "bin" {
+        return None;
+    }
+    bin.parent()
+}
+
+/// How the run ended, as the process exit code nixos-rebuild expects
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    /// Everything was applied.
+    Success = 0,
+
+    /// The command line was invalid.
+    Usage = 1,
+
+    /// The configuration was activated, but some units failed.
+    UnitsFailed = 2,
+
+    /// The configuration was activated, but some changes only take
+    /// effect after a reboot.
+    RebootRecommended = 3,
+
+    /// Nothing, or not everything, could be done.
+    Error = 4,
+}
+
+impl Outcome {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// Add a `what the following units: a, b` line for each non-empty
+/// list, as the Perl script reports them.
+fn describe_units(out: &mut String, what: &str, units: &[String]) {
+    if !units.is_empty() {
+        out.push_str(&format!("{} the following units: {}\n", what, units.join(", ")));
+    }
+}
+
+/// What `dry-activate` prints for a set of mount actions.
+pub fn describe_dry_run(actions: &MountActions) -> String {
+    let mut out = String::new();
+    describe_units(&mut out, "would stop", &actions.stop);
+    describe_units(&mut out, "would restart", &actions.restart);
+    describe_units(&mut out, "would reload", &actions.reload);
+    describe_units(&mut out, "would start", &actions.start);
+    describe_units(&mut out, "would need a reboot to apply", &actions.reboot_required);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Args, UsageError> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn actions() {
+        for &(arg, action) in &[
+            ("switch", Action::Switch),
+            ("boot", Action::Boot),
+            ("test", Action::Test),
+            ("dry-activate", Action::DryActivate),
+        ] {
+            assert_eq!(args(&[arg]), Ok(Args { action, toplevel: None }));
+        }
+        assert!(Action::Switch.installs_boot_loader() && Action::Switch.activates());
+        assert!(Action::Boot.installs_boot_loader() && !Action::Boot.activates());
+        assert!(!Action::Test.installs_boot_loader() && Action::Test.activates());
+        assert!(!Action::DryActivate.installs_boot_loader() && !Action::DryActivate.activates());
+    }
+
+    #[test]
+    fn toplevel_override() {
+        let expected = Ok(Args {
+            action: Action::Test,
+            toplevel: Some(PathBuf::from("/nix/store/abc-nixos-system")),
+        });
+        assert_eq!(args(&["--toplevel", "/nix/store/abc-nixos-system", "test"]), expected);
+        assert_eq!(args(&["test", "--toplevel=/nix/store/abc-nixos-system"]), expected);
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert_eq!(args(&[]), Err(UsageError::MissingAction));
+        assert_eq!(args(&["reboot"]), Err(UsageError::UnknownAction("reboot".to_string())));
+        assert_eq!(args(&["switch", "boot"]), Err(UsageError::UnexpectedArgument("boot".to_string())));
+        assert_eq!(args(&["--force", "switch"]), Err(UsageError::UnknownOption("--force".to_string())));
+        assert_eq!(args(&["switch", "--toplevel"]), Err(UsageError::MissingToplevel));
+        assert_eq!(args(&["switch", "--toplevel="]), Err(UsageError::MissingToplevel));
+        assert_eq!(
+            UsageError::UnknownAction("reboot".to_string()).to_string(),
+            "unknown action \"reboot\""
+        );
+    }
+
+    #[test]
+    fn toplevel_from_exe_path() {
+        assert_eq!(
+            toplevel_from_exe(Path::new("/nix/store/abc-nixos-system/bin/switch-to-configuration")),
+            Some(Path::new("/nix/store/abc-nixos-system"))
+        );
+        assert_eq!(toplevel_from_exe(Path::new("/usr/local/activate")), None);
+        assert_eq!(toplevel_from_exe(Path::new("activate")), None);
+    }
+
+    #[test]
+    fn outcome_codes() {
+        assert_eq!(Outcome::Success.code(), 0);
+        assert_eq!(Outcome::Usage.code(), 1);
+        assert_eq!(Outcome::UnitsFailed.code(), 2);
+        assert_eq!(Outcome::RebootRecommended.code(), 3);
+        assert_eq!(Outcome::Error.code(), 4);
+    }
+
+    #[test]
+    fn dry_run() {
+        let actions = MountActions {
+            stop: vec!["mnt-old.mount".to_string()],
+            start: vec!["mnt-a.mount".to_string(), "mnt-b.mount".to_string()],
+            reload: vec![],
+            restart: vec![],
+            reboot_required: vec!["-.mount".to_string()],
+        };
+        assert_eq!(
+            describe_dry_run(&actions),
+            "would stop the following units: mnt-old.mount
+would start the following units: mnt-a.mount, mnt-b.mount
+would need a reboot to apply the following units: -.mount
+"
+        );
+        assert_eq!(describe_dry_run(&MountActions::default()), "");
+    }
+}
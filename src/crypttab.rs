@@ -0,0 +1,376 @@
+//! Encrypted block devices, as configured in /etc/crypttab.
+//!
+//! ```text
+//! # name   device                                     key file      options
+//! cryptroot UUID=5a7f2ad5-8c2b-4e6e-9a43-2f1f2a2f0d1e none         luks,discard
+//! cryptdata /dev/sdb1                                 /etc/data.key luks,keyfile-timeout=10s
+//! ```
+//!
+//! Each entry is opened by a `systemd-cryptsetup@<name>.service` unit
+//! and appears as `/dev/mapper/<name>`, which is what fstab mounts.
+//! See crypttab(5) and systemd's src/cryptsetup/cryptsetup-generator.c.
+
+use fstab::{FSTabEntry, FSTabFile, MountOptions, Source};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::time::Duration;
+use timespan::parse_timespan;
+use unit_name::escape;
+
+/// Where the key for a volume comes from
+#[derive(Debug, PartialEq, Clone)]
+pub enum KeyFile {
+    /// No key file: the field was `none`, `-` or left out, and the
+    /// passphrase is asked for (or found through `tpm2-device=` and
+    /// friends).
+    None,
+
+    /// A key file on the root filesystem.
+    Path(String),
+
+    /// `/key:UUID=...`, a key file on another device which is mounted
+    /// just to read it.
+    OnDevice { path: String, device: String },
+}
+
+impl KeyFile {
+    /// Classify the third crypttab field.
+    ///
+    /// Like systemd's `split_locationspec`, the field is split on its
+    /// last `:`, and only if what follows is a /dev path or a `UUID=`
+    /// style tag, so a path like `/dev/disk/by-path/pci-0000:00:1f.2`
+    /// stays whole.
+    pub fn parse(field: Option<&str>) -> KeyFile {
+        let field = match field {
+            None | Some("none") | Some("-") | Some("") => return KeyFile::None,
+            Some(field) => field,
+        };
+
+        if let Some(idx) = field.rfind(':') {
+            let device = &field[idx + 1..];
+            if device.starts_with("/dev/") || Source::parse(device).is_tag() {
+                return KeyFile::OnDevice {
+                    path: field[..idx].to_string(),
+                    device: device.to_string(),
+                };
+            }
+        }
+        KeyFile::Path(field.to_string())
+    }
+}
+
+/// A volume to unlock
+#[derive(Debug, PartialEq, Clone)]
+pub struct CryptTabEntry {
+    /// The name of the unlocked device under /dev/mapper.
+    pub name: String,
+
+    /// The encrypted device, as a path or a `UUID=` style tag. See
+    /// `device()`.
+    pub device: String,
+
+    pub key_file: KeyFile,
+
+    /// The options, in the same comma separated format as fstab's.
+    /// Empty if the field was left out.
+    pub options: MountOptions<'static>,
+}
+
+impl CryptTabEntry {
+    /// The encrypted device, classified like an fstab spec.
+    pub fn device(&self) -> Source<'_> {
+        Source::parse(&self.device)
+    }
+
+    /// The path the unlocked device appears at.
+    pub fn mapper_path(&self) -> String {
+        format!("/dev/mapper/{}", self.name)
+    }
+
+    /// The unit systemd-cryptsetup-generator creates to unlock this
+    /// volume.
+    pub fn cryptsetup_unit(&self) -> String {
+        format!("systemd-cryptsetup@{}.service", escape(&self.name))
+    }
+
+    /// Is the volume LUKS formatted? Without `luks`, systemd still
+    /// probes for a LUKS header unless `plain` or another type is set.
+    pub fn is_luks(&self) -> bool {
+        self.options.has("luks")
+    }
+
+    /// Are discards passed down to the encrypted device?
+    pub fn discard(&self) -> bool {
+        self.options.has("discard")
+    }
+
+    pub fn noauto(&self) -> bool {
+        self.options.has("noauto")
+    }
+
+    pub fn nofail(&self) -> bool {
+        self.options.has("nofail")
+    }
+
+    /// `tpm2-device=`: `auto` or the path of a TPM2 device to unlock
+    /// with.
+    pub fn tpm2_device(&self) -> Option<&str> {
+        self.options.get("tpm2-device")
+    }
+
+    /// `keyfile-timeout=`: how long to wait for the key file's device
+    /// before asking for a passphrase. None if unset or invalid.
+    pub fn keyfile_timeout(&self) -> Option<Duration> {
+        self.options.get("keyfile-timeout").and_then(parse_timespan)
+    }
+}
+
+/// Why a line of a crypttab could not be parsed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CryptTabErrorReason {
+    /// The line has no device field.
+    TooFewFields,
+
+    /// The line has more than four fields.
+    TooManyFields,
+}
+
+impl fmt::Display for CryptTabErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            CryptTabErrorReason::TooFewFields => "too few fields",
+            CryptTabErrorReason::TooManyFields => "too many fields",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// A line which could not be parsed, and why
+#[derive(Debug, PartialEq, Clone)]
+pub struct CryptTabError {
+    /// The 1-based line number.
+    pub line: usize,
+
+    /// The line exactly as it appeared in the file.
+    pub raw: String,
+
+    pub reason: CryptTabErrorReason,
+}
+
+impl fmt::Display for CryptTabError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "crypttab line {}: {}: {:?}", self.line, self.reason, self.raw)
+    }
+}
+
+impl Error for CryptTabError {}
+
+/// Parse a single line of a crypttab. Comments and blank lines are
+/// `Ok(None)`.
+///
+/// Fields are separated by whitespace. Only the name and device are
+/// required; the key file and options may be left out.
+pub fn parse_crypttab_line(line: &str) -> Result<Option<CryptTabEntry>, CryptTabErrorReason> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let fields = line.split_whitespace().collect::<Vec<&str>>();
+    if fields.len() < 2 {
+        return Err(CryptTabErrorReason::TooFewFields);
+    } else if fields.len() > 4 {
+        return Err(CryptTabErrorReason::TooManyFields);
+    }
+
+    Ok(Some(CryptTabEntry {
+        name: fields[0].to_string(),
+        device: fields[1].to_string(),
+        key_file: KeyFile::parse(fields.get(2).cloned()),
+        options: MountOptions::parse(fields.get(3).cloned().unwrap_or("")).into_owned(),
+    }))
+}
+
+/// Every volume in a crypttab
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CryptTab {
+    pub entries: Vec<CryptTabEntry>,
+}
+
+impl CryptTab {
+    /// Parse a crypttab from any reader. An invalid line is an
+    /// `InvalidData` error whose inner error is a `CryptTabError`.
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<CryptTab> {
+        let mut entries = vec![];
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let entry = parse_crypttab_line(&line).map_err(|reason| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    CryptTabError {
+                        line: idx + 1,
+                        raw: line.clone(),
+                        reason,
+                    },
+                )
+            })?;
+            entries.extend(entry);
+        }
+        Ok(CryptTab { entries })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<CryptTab> {
+        CryptTab::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn find(&self, name: &str) -> Option<&CryptTabEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// The volume an fstab entry mounts, if its spec is
+    /// `/dev/mapper/<name>`.
+    pub fn find_for_fstab(&self, entry: &FSTabEntry) -> Option<&CryptTabEntry> {
+        let name = entry.spec.strip_prefix("/dev/mapper/")?;
+        self.find(name)
+    }
+
+    /// Every fstab entry, including swap, which is on one of these
+    /// volumes, with its volume.
+    pub fn link_fstab<'a, 'b>(&'a self, fstab: &'a FSTabFile<'b>) -> Vec<(&'a FSTabEntry<'b>, &'a CryptTabEntry)> {
+        fstab.entries.iter()
+            .filter_map(|entry| self.find_for_fstab(entry).map(|crypt| (entry, crypt)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::fixtures::UTILLINUX_FSTAB;
+    use fstab::parse_fstab;
+
+    const CRYPTTAB: &str = "# <name> <device> <password> <options>
+cryptroot UUID=5a7f2ad5-8c2b-4e6e-9a43-2f1f2a2f0d1e none luks,discard,tpm2-device=auto
+foo       /dev/sdb1                                 /etc/foo.key luks,keyfile-timeout=10s
+
+  swap    PARTUUID=0f1e2d3c-01 /dev/urandom swap,cipher=aes-xts-plain64,size=256
+usb-key   /dev/sdc1 /key.bin:LABEL=usb
+bare      /dev/sdd1
+";
+
+    #[test]
+    fn parse_line() {
+        assert_eq!(
+            parse_crypttab_line("cryptroot UUID=5a7f2ad5 - luks,discard"),
+            Ok(Some(CryptTabEntry {
+                name: "cryptroot".to_string(),
+                device: "UUID=5a7f2ad5".to_string(),
+                key_file: KeyFile::None,
+                options: "luks,discard".into(),
+            }))
+        );
+        assert_eq!(parse_crypttab_line("  # comment"), Ok(None));
+        assert_eq!(parse_crypttab_line(""), Ok(None));
+        assert_eq!(parse_crypttab_line("onlyname"), Err(CryptTabErrorReason::TooFewFields));
+        assert_eq!(
+            parse_crypttab_line("a /dev/sda1 none luks extra"),
+            Err(CryptTabErrorReason::TooManyFields)
+        );
+    }
+
+    #[test]
+    fn key_files() {
+        let table = CryptTab::from_reader(CRYPTTAB.as_bytes()).unwrap();
+        let key_files = table.entries.iter().map(|entry| &entry.key_file).collect::<Vec<_>>();
+        assert_eq!(
+            key_files,
+            vec![
+                &KeyFile::None,
+                &KeyFile::Path("/etc/foo.key".to_string()),
+                &KeyFile::Path("/dev/urandom".to_string()),
+                &KeyFile::OnDevice {
+                    path: "/key.bin".to_string(),
+                    device: "LABEL=usb".to_string(),
+                },
+                &KeyFile::None,
+            ]
+        );
+    }
+
+    #[test]
+    fn key_file_location_specs() {
+        assert_eq!(
+            KeyFile::parse(Some("/dev/disk/by-path/pci-0000:00:1f.2-part1")),
+            KeyFile::Path("/dev/disk/by-path/pci-0000:00:1f.2-part1".to_string())
+        );
+        assert_eq!(
+            KeyFile::parse(Some("/key:UUID=5a7f2ad5-8c2b")),
+            KeyFile::OnDevice {
+                path: "/key".to_string(),
+                device: "UUID=5a7f2ad5-8c2b".to_string(),
+            }
+        );
+        assert_eq!(
+            KeyFile::parse(Some("/keys/a:b.key:/dev/sdc1")),
+            KeyFile::OnDevice {
+                path: "/keys/a:b.key".to_string(),
+                device: "/dev/sdc1".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn devices_and_options() {
+        let table = CryptTab::from_reader(CRYPTTAB.as_bytes()).unwrap();
+
+        let root = table.find("cryptroot").unwrap();
+        assert_eq!(root.device(), Source::Uuid("5a7f2ad5-8c2b-4e6e-9a43-2f1f2a2f0d1e"));
+        assert!(root.is_luks());
+        assert!(root.discard());
+        assert_eq!(root.tpm2_device(), Some("auto"));
+        assert_eq!(root.keyfile_timeout(), None);
+
+        let foo = table.find("foo").unwrap();
+        assert_eq!(foo.device(), Source::Path("/dev/sdb1"));
+        assert!(!foo.discard());
+        assert_eq!(foo.keyfile_timeout(), Some(Duration::from_secs(10)));
+
+        let swap = table.find("swap").unwrap();
+        assert_eq!(swap.device(), Source::PartUuid("0f1e2d3c-01"));
+        assert_eq!(swap.options.get("size"), Some("256"));
+
+        assert!(table.find("bare").unwrap().options.is_empty());
+    }
+
+    #[test]
+    fn units() {
+        let entry = parse_crypttab_line("luks-data-1 /dev/sdb1").unwrap().unwrap();
+        assert_eq!(entry.mapper_path(), "/dev/mapper/luks-data-1");
+        assert_eq!(entry.cryptsetup_unit(), "systemd-cryptsetup@luks\\x2ddata\\x2d1.service");
+    }
+
+    #[test]
+    fn link_fstab_entries() {
+        let table = CryptTab::from_reader(CRYPTTAB.as_bytes()).unwrap();
+        let fstab = parse_fstab(UTILLINUX_FSTAB.lines());
+        let linked = table.link_fstab(&fstab)
+            .into_iter()
+            .map(|(entry, crypt)| (&entry.file[..], crypt.cryptsetup_unit()))
+            .collect::<Vec<_>>();
+        assert_eq!(linked, vec![("/home/foo", "systemd-cryptsetup@foo.service".to_string())]);
+
+        let swap = parse_fstab("/dev/mapper/swap none swap\n/dev/mapper/other /x ext4\n".lines());
+        assert_eq!(table.link_fstab(&swap).len(), 1);
+        assert_eq!(table.find_for_fstab(&swap.entries[1]), None);
+    }
+
+    #[test]
+    fn read_table_error() {
+        let err = CryptTab::from_reader("ok /dev/sda1\nbroken\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "crypttab line 2: too few fields: \"broken\"");
+    }
+}
@@ -0,0 +1,200 @@
+//! Resolving fstab sources to the block devices they refer to.
+//!
+//! udev maintains /dev/disk/by-uuid, by-label, by-partuuid,
+//! by-partlabel and by-id as symlinks to the real device nodes. Two
+//! different looking specs, like `UUID=...` and `/dev/disk/by-label/nixos`,
+//! can point at the same device, and activation must not treat that as
+//! a change.
+
+use fstab::Source;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// How many symlinks to follow before giving up, like the kernel's
+/// MAXSYMLINKS.
+const MAX_SYMLINKS: usize = 40;
+
+/// Resolves sources through the udev symlinks under a /dev/disk
+/// directory.
+///
+/// The directory is configurable so tests can build a fake tree.
+/// Paths are reported as they would appear on the real system, ie:
+/// `/dev/sda1`, no matter where the tree actually is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceResolver {
+    root: PathBuf,
+}
+
+impl Default for DeviceResolver {
+    fn default() -> DeviceResolver {
+        DeviceResolver::new("/dev/disk")
+    }
+}
+
+/// Resolve `.` and `..` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+impl DeviceResolver {
+    /// Resolve symlinks under `root` in place of /dev/disk.
+    pub fn new<P: Into<PathBuf>>(root: P) -> DeviceResolver {
+        DeviceResolver { root: root.into() }
+    }
+
+    /// Where a /dev/disk path actually lives, or None if it is outside
+    /// /dev/disk.
+    fn physical(&self, logical: &Path) -> Option<PathBuf> {
+        logical
+            .strip_prefix("/dev/disk")
+            .ok()
+            .map(|relative| self.root.join(relative))
+    }
+
+    /// The canonical device node for a source, following the udev
+    /// symlinks under /dev/disk.
+    ///
+    /// Paths outside /dev/disk, like `/dev/sda1` or `/dev/mapper/foo`,
+    /// are returned unchanged. Sources which aren't block devices,
+    /// like `tmpfs` or NFS exports, are an `InvalidInput` error, and a
+    /// missing symlink is `NotFound`.
+    pub fn resolve(&self, source: &Source) -> io::Result<PathBuf> {
+        let device = source.device_path().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} is not a block device", source),
+            )
+        })?;
+
+        let mut logical = PathBuf::from(device);
+        for _ in 0..MAX_SYMLINKS {
+            let physical = match self.physical(&logical) {
+                Some(physical) => physical,
+                None => return Ok(logical),
+            };
+
+            if !fs::symlink_metadata(&physical)?.file_type().is_symlink() {
+                return Ok(logical);
+            }
+
+            let target = fs::read_link(&physical)?;
+            let parent = logical.parent().unwrap_or_else(|| Path::new("/")).to_path_buf();
+            logical = normalize(&parent.join(target));
+        }
+
+        Err(io::Error::other(format!(
+            "too many levels of symbolic links resolving {:?}",
+            source
+        )))
+    }
+
+    /// Do two sources refer to the same device node?
+    pub fn same_device(&self, left: &Source, right: &Source) -> io::Result<bool> {
+        Ok(self.resolve(left)? == self.resolve(right)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::TempDir;
+
+    fn fake_dev_disk() -> TempDir {
+        let tmp = TempDir::new();
+        tmp.write("dev/sda1", "");
+        tmp.write("dev/sda2", "");
+        tmp.symlink(
+            "dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc",
+            "../../sda1",
+        );
+        tmp.symlink("dev/disk/by-uuid/2D03-B634", "../../sda2");
+        tmp.symlink("dev/disk/by-label/nixos", "../../sda1");
+        tmp.symlink("dev/disk/by-label/my\\x20disk", "../../sda2");
+        tmp.symlink("dev/disk/by-partuuid/5a7f2ad5-01", "/dev/sda1");
+        tmp.symlink("dev/disk/by-partlabel/root", "../by-label/nixos");
+        tmp.symlink("dev/disk/by-id/loop", "../by-id/loop");
+        tmp
+    }
+
+    fn resolver(tmp: &TempDir) -> DeviceResolver {
+        DeviceResolver::new(tmp.path().join("dev/disk"))
+    }
+
+    #[test]
+    fn resolve_tags() {
+        let tmp = fake_dev_disk();
+        let resolver = resolver(&tmp);
+        let sda1 = PathBuf::from("/dev/sda1");
+        let sda2 = PathBuf::from("/dev/sda2");
+
+        assert_eq!(
+            resolver.resolve(&Source::Uuid("3aa72460-7d05-4bd4-861f-6ef8b82082dc")).unwrap(),
+            sda1
+        );
+        assert_eq!(resolver.resolve(&Source::Label("nixos")).unwrap(), sda1);
+        assert_eq!(resolver.resolve(&Source::Label("my disk")).unwrap(), sda2);
+        assert_eq!(resolver.resolve(&Source::PartUuid("5a7f2ad5-01")).unwrap(), sda1);
+        assert_eq!(resolver.resolve(&Source::PartLabel("root")).unwrap(), sda1);
+    }
+
+    #[test]
+    fn resolve_paths() {
+        let tmp = fake_dev_disk();
+        let resolver = resolver(&tmp);
+        assert_eq!(
+            resolver.resolve(&Source::Path("/dev/disk/by-label/nixos")).unwrap(),
+            PathBuf::from("/dev/sda1")
+        );
+        assert_eq!(
+            resolver.resolve(&Source::Path("/dev/mapper/foo")).unwrap(),
+            PathBuf::from("/dev/mapper/foo")
+        );
+    }
+
+    #[test]
+    fn resolve_errors() {
+        let tmp = fake_dev_disk();
+        let resolver = resolver(&tmp);
+        assert_eq!(
+            resolver.resolve(&Source::Uuid("missing")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            resolver.resolve(&Source::Virtual("tmpfs")).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            resolver.resolve(&Source::Id("loop")).unwrap_err().kind(),
+            io::ErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn same_device() {
+        let tmp = fake_dev_disk();
+        let resolver = resolver(&tmp);
+        assert!(resolver
+            .same_device(
+                &Source::parse("UUID=3aa72460-7d05-4bd4-861f-6ef8b82082dc"),
+                &Source::parse("/dev/disk/by-label/nixos"),
+            )
+            .unwrap());
+        assert!(!resolver
+            .same_device(
+                &Source::parse("UUID=2D03-B634"),
+                &Source::parse("/dev/disk/by-label/nixos"),
+            )
+            .unwrap());
+    }
+}
@@ -1,4 +1,37 @@
+use mangle::unmangle;
 use regex::Regex;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+
+pub mod bind;
+pub mod diff;
+pub mod document;
+pub mod fragments;
+pub mod fs_type;
+pub mod lint;
+pub mod options;
+pub mod order;
+pub mod plan;
+pub mod read;
+pub mod source;
+pub mod systemd_options;
+pub mod write;
+#[cfg(test)]
+pub mod fixtures;
+
+pub use self::bind::OverlayDirs;
+pub use self::diff::{diff, EntryChange, FstabDiff};
+pub use self::document::{FstabDocument, FstabLine};
+pub use self::fragments::{Conflict, MergedFstab, Provenance, SourcedEntry};
+pub use self::lint::{lint, lint_lines, LintKind, LintWarning};
+pub use self::options::{MountOption, MountOptions};
+pub use self::order::{CycleError, MountGraph};
+pub use self::plan::{plan, MountActions};
+pub use self::read::InvalidFstab;
+pub use self::source::Source;
+pub use self::systemd_options::{InvalidSystemdOption, SystemdMountOptions};
+pub use self::write::{DisplayFstab, WriteOptions};
 
 lazy_static! {
     static ref COMMENT_REMOVAL_REGEXP: Regex = Regex::new(
@@ -8,29 +41,29 @@ lazy_static! {
 
 
 /// A valid, parsed fstab file
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct FSTabFile<'a> {
     /// All the entries which were validly parsed
     pub entries: Vec<FSTabEntry<'a>>,
 }
 
 /// A filesystem line
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct FSTabEntry<'a> {
     /// This field describes the block special device or
-    /// filesystem to be mounted.
-    pub spec: &'a str,
+    /// filesystem to be mounted. Octal escapes are decoded.
+    pub spec: Cow<'a, str>,
 
     /// This field  describes the mount point (target) for the
-    /// filesystem.
-    pub file: &'a str,
+    /// filesystem. Octal escapes are decoded.
+    pub file: Cow<'a, str>,
 
     /// This field describes the type of the filesystem.
-    pub fs_type: &'a str,
+    pub fs_type: Cow<'a, str>,
 
     /// This field describes the mount options associated with the
     /// filesystem, in a comma separated list.
-    pub options: &'a str,
+    pub options: MountOptions<'a>,
 
     /// This field is used by dump(8) to determine which filesystems
     /// need to be dumped. Defaults to 0.
@@ -41,6 +74,107 @@ pub struct FSTabEntry<'a> {
     pub fsck_pass: i8,
 }
 
+/// Why a line of an fstab could not be parsed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FstabErrorReason {
+    /// The line has fewer than the three required fields.
+    TooFewFields,
+
+    /// The line has more than six fields.
+    TooManyFields,
+
+    /// The dump field isn't a number which fits in an i8.
+    InvalidDump,
+
+    /// The fsck pass field isn't a number which fits in an i8.
+    InvalidFsckPass,
+
+    /// An octal escape in the spec or file field decoded to something
+    /// which isn't valid UTF-8.
+    BadEscape,
+}
+
+impl fmt::Display for FstabErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            FstabErrorReason::TooFewFields => "too few fields",
+            FstabErrorReason::TooManyFields => "too many fields",
+            FstabErrorReason::InvalidDump => "dump field is not a number between -128 and 127",
+            FstabErrorReason::InvalidFsckPass => "fsck pass field is not a number between -128 and 127",
+            FstabErrorReason::BadEscape => "octal escape is not valid UTF-8",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// A line which could not be parsed, and why
+#[derive(Debug, PartialEq, Clone)]
+pub struct FstabError {
+    /// The 1-based line number.
+    pub line: usize,
+
+    /// The line exactly as it appeared in the file.
+    pub raw: String,
+
+    pub reason: FstabErrorReason,
+}
+
+impl fmt::Display for FstabError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}: {:?}", self.line, self.reason, self.raw)
+    }
+}
+
+impl Error for FstabError {}
+
+/// Parse a single line of an fstab, reporting why invalid lines are
+/// invalid.
+///
+/// Comments and blank lines are `Ok(None)`. See `parse_fstab_line`
+/// for the details of the format. Unlike `parse_fstab_line`, a dump or
+/// fsck pass field which isn't a number is an error.
+pub fn try_parse_fstab_line<'a>(fstab: &'a str) -> Result<Option<FSTabEntry<'a>>, FstabErrorReason> {
+    parse_line(fstab, true)
+}
+
+/// Parse a number field, which is 0 if it is left out. If `strict` is
+/// false, a field which isn't a number is 0 too, as in util-linux.
+fn parse_number(field: Option<&&str>, strict: bool, reason: FstabErrorReason) -> Result<i8, FstabErrorReason> {
+    match field.cloned().unwrap_or("0").parse::<i8>() {
+        Ok(number) => Ok(number),
+        Err(_) if !strict => Ok(0),
+        Err(_) => Err(reason),
+    }
+}
+
+fn parse_line<'a>(fstab: &'a str, strict: bool) -> Result<Option<FSTabEntry<'a>>, FstabErrorReason> {
+    if COMMENT_REMOVAL_REGEXP.is_match(fstab) {
+        return Ok(None)
+    }
+
+    let fields = fstab.split_whitespace().collect::<Vec<&str>>();
+    if fields.is_empty() {
+        return Ok(None)
+    } else if fields.len() < 3 {
+        return Err(FstabErrorReason::TooFewFields)
+    } else if fields.len() > 6 {
+        return Err(FstabErrorReason::TooManyFields)
+    }
+
+    Ok(Some(FSTabEntry {
+        spec: unmangle(fields[0]).ok_or(FstabErrorReason::BadEscape)?,
+        file: unmangle(fields[1]).ok_or(FstabErrorReason::BadEscape)?,
+        fs_type: fields[2].into(),
+
+        // "options" is required by the manual, but it seems they can
+        // be ommitted based on the util-linux source
+        // see: libmount/src/tab_parse.c
+        options: MountOptions::parse(fields.get(3).cloned().unwrap_or("")),
+        dump: parse_number(fields.get(4), strict, FstabErrorReason::InvalidDump)?,
+        fsck_pass: parse_number(fields.get(5), strict, FstabErrorReason::InvalidFsckPass)?,
+    }))
+}
+
 /// Parse a single line of an fstab
 ///
 /// According to `man fstab` each line is a series of space-separated
@@ -48,53 +182,91 @@ pub struct FSTabEntry<'a> {
 /// skipped.
 ///
 /// According to the source code (libmount/src/tab_parse.c) invalid
-/// lines are simply skipped.
+/// lines are simply skipped. Dump or fsck pass fields which aren't
+/// numbers are treated as 0; `parse_fstab_strict` reports them.
 ///
-/// Note: According to the documentation, an fstab's `file` field can
-/// contain spaces and tabs if they are represented by \040 and \011.
-/// This function doesn't decode these octal characters.
+/// According to the documentation, an fstab's `spec` and `file` fields
+/// can contain spaces and tabs if they are represented by \040 and
+/// \011. These octal escapes are decoded the same way util-linux's
+/// `unmangle` does, and a line whose escapes don't decode to valid
+/// UTF-8 is treated as invalid.
 pub fn parse_fstab_line<'a>(fstab: &'a str) -> Option<FSTabEntry<'a>> {
-    if COMMENT_REMOVAL_REGEXP.is_match(fstab) {
-        return None
-    }
-
-    let mut parts = fstab.split_whitespace();
-    let result = Some(FSTabEntry {
-        spec: parts.next()?,
-        file: parts.next()?,
-        fs_type: parts.next()?,
-
-        // "options" is required by the manual, but it seems they can
-        // be ommitted based on the util-linux source
-        // see: libmount/src/tab_parse.c
-        options: parts.next().unwrap_or(""),
-        dump: parts.next().unwrap_or("0").parse::<i8>().unwrap_or(0),
-        fsck_pass: parts.next().unwrap_or("0").parse::<i8>().unwrap_or(0),
-    });
-    if parts.next() == None {
-        return result
-    } else {
-        return None
-    }
+    parse_line(fstab, false).ok().and_then(|entry| entry)
 }
 
 /// Pass in an iterator of ftab lines, ie: "my\nfile".lines()
 /// and get back a parsed representation of the file.
 ///
+/// To parse from a `File` or other reader, use `FSTabFile::from_reader`.
+///
 /// See parse_fstab_line for more information about edge cases and
 /// specific behavior of this implementation.
 pub fn parse_fstab<'a, T: Iterator<Item = &'a str>>(fstab_lines: T) -> FSTabFile<'a> {
     FSTabFile {
         entries: fstab_lines
-            .map(|line| parse_fstab_line(line))
-            .filter_map(|x|x).collect::<Vec<FSTabEntry>>()
+            .filter_map(parse_fstab_line)
+            .collect::<Vec<FSTabEntry>>()
     }
 }
 
+/// Like parse_fstab, but fail if any line is invalid instead of
+/// skipping it.
+///
+/// Use this for files we generated ourselves: an entry silently
+/// disappearing from a corrupt fstab would otherwise look like a
+/// filesystem which should be unmounted.
+pub fn parse_fstab_strict<'a, T: Iterator<Item = &'a str>>(fstab_lines: T) -> Result<FSTabFile<'a>, Vec<FstabError>> {
+    let mut entries = vec![];
+    let mut errors = vec![];
+
+    for (idx, line) in fstab_lines.enumerate() {
+        match try_parse_fstab_line(line) {
+            Ok(Some(entry)) => entries.push(entry),
+            Ok(None) => {}
+            Err(reason) => errors.push(FstabError {
+                line: idx + 1,
+                raw: line.to_string(),
+                reason,
+            }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(FSTabFile { entries })
+    } else {
+        Err(errors)
+    }
+}
+
+/// Is `path` strictly below `parent`, comparing whole components?
+/// `/home/foo` is under `/home` and `/`, but `/homes` isn't under
+/// `/home`. Trailing slashes are ignored.
+pub fn path_is_under(path: &str, parent: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    let parent = parent.trim_end_matches('/');
+    path.len() > parent.len()
+        && path.starts_with(parent)
+        && path.as_bytes()[parent.len()] == b'/'
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn path_is_under_components() {
+        assert!(path_is_under("/home/foo", "/home"));
+        assert!(path_is_under("/home/foo", "/home/"));
+        assert!(path_is_under("/home/foo/", "/home"));
+        assert!(path_is_under("/home", "/"));
+        assert!(path_is_under("/home/foo/bar", "/home"));
+        assert!(!path_is_under("/homes", "/home"));
+        assert!(!path_is_under("/home", "/home"));
+        assert!(!path_is_under("/home/", "/home"));
+        assert!(!path_is_under("/", "/"));
+        assert!(!path_is_under("/home", "/home/foo"));
+    }
+
     #[test]
     fn parse_fstab_line_comment() {
         assert_eq!(
@@ -112,10 +284,10 @@ mod tests {
         assert_eq!(
             parse_fstab_line("/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc / ext4 defaults 0 1"),
             Some(FSTabEntry {
-                spec: "/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc",
-                file: "/",
-                fs_type: "ext4",
-                options: "defaults",
+                spec: "/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc".into(),
+                file: "/".into(),
+                fs_type: "ext4".into(),
+                options: "defaults".into(),
                 dump: 0,
                 fsck_pass: 1
             })
@@ -127,10 +299,10 @@ mod tests {
         assert_eq!(
             parse_fstab_line("/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc / ext4 defaults"),
             Some(FSTabEntry {
-                spec: "/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc",
-                file: "/",
-                fs_type: "ext4",
-                options: "defaults",
+                spec: "/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc".into(),
+                file: "/".into(),
+                fs_type: "ext4".into(),
+                options: "defaults".into(),
                 dump: 0,
                 fsck_pass: 0,
             })
@@ -142,16 +314,57 @@ mod tests {
         assert_eq!(
             parse_fstab_line("/dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e none swap"),
             Some(FSTabEntry {
-                spec: "/dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e",
-                file: "none",
-                fs_type: "swap",
-                options: "",
+                spec: "/dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e".into(),
+                file: "none".into(),
+                fs_type: "swap".into(),
+                options: "".into(),
+                dump: 0,
+                fsck_pass: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_fstab_line_octal_escapes() {
+        assert_eq!(
+            parse_fstab_line("LABEL=My\\040Disk /mnt/My\\040Disk\\011Tab ext4 defaults 0 2"),
+            Some(FSTabEntry {
+                spec: "LABEL=My Disk".into(),
+                file: "/mnt/My Disk\tTab".into(),
+                fs_type: "ext4".into(),
+                options: "defaults".into(),
+                dump: 0,
+                fsck_pass: 2,
+            })
+        );
+        assert_eq!(
+            parse_fstab_line("/dev/sda1 /mnt/back\\134slash\\012newline ext4"),
+            Some(FSTabEntry {
+                spec: "/dev/sda1".into(),
+                file: "/mnt/back\\slash\nnewline".into(),
+                fs_type: "ext4".into(),
+                options: "".into(),
                 dump: 0,
                 fsck_pass: 0,
             })
         );
     }
 
+    #[test]
+    fn parse_fstab_line_borrows_unescaped_fields() {
+        let entry = parse_fstab_line("/dev/sda1 /mnt ext4 defaults").unwrap();
+        assert!(matches!(entry.spec, Cow::Borrowed(_)));
+        assert!(matches!(entry.file, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn parse_fstab_line_invalid_escape() {
+        assert_eq!(
+            parse_fstab_line("/dev/sda1 /mnt/\\377 ext4 defaults 0 2"),
+            None,
+        );
+    }
+
     #[test]
     fn parse_fstab_line_invalid_too_many_fields() {
         assert_eq!(
@@ -199,26 +412,26 @@ mod tests {
             FSTabFile {
                 entries: vec![
                     FSTabEntry {
-                        spec: "/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc",
-                        file: "/",
-                        fs_type: "ext4",
-                        options: "defaults",
+                        spec: "/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc".into(),
+                        file: "/".into(),
+                        fs_type: "ext4".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 1
                     },
                     FSTabEntry {
-                        spec: "/dev/disk/by-uuid/2D03-B634",
-                        file: "/boot",
-                        fs_type: "vfat",
-                        options: "defaults",
+                        spec: "/dev/disk/by-uuid/2D03-B634".into(),
+                        file: "/boot".into(),
+                        fs_type: "vfat".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 2
                     },
                     FSTabEntry {
-                        spec: "/dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e",
-                        file: "none",
-                        fs_type: "swap",
-                        options: "",
+                        spec: "/dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e".into(),
+                        file: "none".into(),
+                        fs_type: "swap".into(),
+                        options: "".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
@@ -259,90 +472,90 @@ foo.com:/mnt/share	/mnt/remote		nfs	noauto
             FSTabFile {
                 entries: vec![
                     FSTabEntry {
-                        spec: "UUID=d3a8f783-df75-4dc8-9163-975a891052c0",
-                        file: "/",
-                        fs_type: "ext3",
-                        options: "noatime,defaults",
+                        spec: "UUID=d3a8f783-df75-4dc8-9163-975a891052c0".into(),
+                        file: "/".into(),
+                        fs_type: "ext3".into(),
+                        options: "noatime,defaults".into(),
                         dump: 1,
                         fsck_pass: 1
                     },
                     FSTabEntry {
-                        spec: "UUID=fef7ccb3-821c-4de8-88dc-71472be5946f",
-                        file: "/boot",
-                        fs_type: "ext3",
-                        options: "noatime,defaults",
+                        spec: "UUID=fef7ccb3-821c-4de8-88dc-71472be5946f".into(),
+                        file: "/boot".into(),
+                        fs_type: "ext3".into(),
+                        options: "noatime,defaults".into(),
                         dump: 1,
                         fsck_pass: 2
                     },
                     FSTabEntry {
-                        spec: "UUID=1f2aa318-9c34-462e-8d29-260819ffd657",
-                        file: "swap",
-                        fs_type: "swap",
-                        options: "defaults",
+                        spec: "UUID=1f2aa318-9c34-462e-8d29-260819ffd657".into(),
+                        file: "swap".into(),
+                        fs_type: "swap".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "tmpfs",
-                        file: "/dev/shm",
-                        fs_type: "tmpfs",
-                        options: "defaults",
+                        spec: "tmpfs".into(),
+                        file: "/dev/shm".into(),
+                        fs_type: "tmpfs".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "devpts",
-                        file: "/dev/pts",
-                        fs_type: "devpts",
-                        options: "gid=5,mode=620",
+                        spec: "devpts".into(),
+                        file: "/dev/pts".into(),
+                        fs_type: "devpts".into(),
+                        options: "gid=5,mode=620".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "sysfs",
-                        file: "/sys",
-                        fs_type: "sysfs",
-                        options: "defaults",
+                        spec: "sysfs".into(),
+                        file: "/sys".into(),
+                        fs_type: "sysfs".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "proc",
-                        file: "/proc",
-                        fs_type: "proc",
-                        options: "defaults",
+                        spec: "proc".into(),
+                        file: "/proc".into(),
+                        fs_type: "proc".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "/dev/mapper/foo",
-                        file: "/home/foo",
-                        fs_type: "ext4",
-                        options: "noatime,defaults",
+                        spec: "/dev/mapper/foo".into(),
+                        file: "/home/foo".into(),
+                        fs_type: "ext4".into(),
+                        options: "noatime,defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "foo.com:/mnt/share",
-                        file: "/mnt/remote",
-                        fs_type: "nfs",
-                        options: "noauto",
+                        spec: "foo.com:/mnt/share".into(),
+                        file: "/mnt/remote".into(),
+                        fs_type: "nfs".into(),
+                        options: "noauto".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "//bar.com/gogogo",
-                        file: "/mnt/gogogo",
-                        fs_type: "cifs",
-                        options: "user=SRGROUP/baby,noauto",
+                        spec: "//bar.com/gogogo".into(),
+                        file: "/mnt/gogogo".into(),
+                        fs_type: "cifs".into(),
+                        options: "user=SRGROUP/baby,noauto".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "/dev/foo",
-                        file: "/any/foo/",
-                        fs_type: "auto",
-                        options: "defaults",
+                        spec: "/dev/foo".into(),
+                        file: "/any/foo/".into(),
+                        fs_type: "auto".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
@@ -375,82 +588,82 @@ foo.com:/mnt/share	/mnt/remote		nfs	noauto
             FSTabFile {
                 entries: vec![
                     FSTabEntry {
-                        spec: "UUID=d3a8f783-df75-4dc8-9163-975a891052c0",
-                        file: "/",
-                        fs_type: "ext3",
-                        options: "noatime,defaults",
+                        spec: "UUID=d3a8f783-df75-4dc8-9163-975a891052c0".into(),
+                        file: "/".into(),
+                        fs_type: "ext3".into(),
+                        options: "noatime,defaults".into(),
                         dump: 1,
                         fsck_pass: 1
                     },
                     FSTabEntry {
-                        spec: "UUID=fef7ccb3-821c-4de8-88dc-71472be5946f",
-                        file: "/boot",
-                        fs_type: "ext3",
-                        options: "noatime,defaults",
+                        spec: "UUID=fef7ccb3-821c-4de8-88dc-71472be5946f".into(),
+                        file: "/boot".into(),
+                        fs_type: "ext3".into(),
+                        options: "noatime,defaults".into(),
                         dump: 1,
                         fsck_pass: 2
                     },
                     FSTabEntry {
-                        spec: "UUID=1f2aa318-9c34-462e-8d29-260819ffd657",
-                        file: "swap",
-                        fs_type: "swap",
-                        options: "defaults",
+                        spec: "UUID=1f2aa318-9c34-462e-8d29-260819ffd657".into(),
+                        file: "swap".into(),
+                        fs_type: "swap".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "tmpfs",
-                        file: "/dev/shm",
-                        fs_type: "tmpfs",
-                        options: "defaults",
+                        spec: "tmpfs".into(),
+                        file: "/dev/shm".into(),
+                        fs_type: "tmpfs".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "devpts",
-                        file: "/dev/pts",
-                        fs_type: "devpts",
-                        options: "gid=5,mode=620",
+                        spec: "devpts".into(),
+                        file: "/dev/pts".into(),
+                        fs_type: "devpts".into(),
+                        options: "gid=5,mode=620".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "sysfs",
-                        file: "/sys",
-                        fs_type: "sysfs",
-                        options: "defaults",
+                        spec: "sysfs".into(),
+                        file: "/sys".into(),
+                        fs_type: "sysfs".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "proc",
-                        file: "/proc",
-                        fs_type: "proc",
-                        options: "defaults",
+                        spec: "proc".into(),
+                        file: "/proc".into(),
+                        fs_type: "proc".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "/dev/mapper/foo",
-                        file: "/home/foo",
-                        fs_type: "ext4",
-                        options: "noatime,defaults",
+                        spec: "/dev/mapper/foo".into(),
+                        file: "/home/foo".into(),
+                        fs_type: "ext4".into(),
+                        options: "noatime,defaults".into(),
                         dump: 1,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "foo.com:/mnt/share",
-                        file: "/mnt/remote",
-                        fs_type: "nfs",
-                        options: "noauto",
+                        spec: "foo.com:/mnt/share".into(),
+                        file: "/mnt/remote".into(),
+                        fs_type: "nfs".into(),
+                        options: "noauto".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "//bar.com/gogogo",
-                        file: "/mnt/gogogo",
-                        fs_type: "cifs",
-                        options: "user=SRGROUP/baby,noauto",
+                        spec: "//bar.com/gogogo".into(),
+                        file: "/mnt/gogogo".into(),
+                        fs_type: "cifs".into(),
+                        options: "user=SRGROUP/baby,noauto".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
@@ -460,6 +673,142 @@ foo.com:/mnt/share	/mnt/remote		nfs	noauto
     }
 
 
+    #[test]
+    fn try_parse_fstab_line_reasons() {
+        assert_eq!(try_parse_fstab_line(""), Ok(None));
+        assert_eq!(try_parse_fstab_line("   # comment"), Ok(None));
+        assert_eq!(
+            try_parse_fstab_line("bug"),
+            Err(FstabErrorReason::TooFewFields)
+        );
+        assert_eq!(
+            try_parse_fstab_line("/dev/sda1 /"),
+            Err(FstabErrorReason::TooFewFields)
+        );
+        assert_eq!(
+            try_parse_fstab_line("/dev/sda1 / ext4 defaults 0 1 # foo"),
+            Err(FstabErrorReason::TooManyFields)
+        );
+        assert_eq!(
+            try_parse_fstab_line("/dev/sda1 / ext4 defaults x 1"),
+            Err(FstabErrorReason::InvalidDump)
+        );
+        assert_eq!(
+            try_parse_fstab_line("/dev/sda1 / ext4 defaults 0 one"),
+            Err(FstabErrorReason::InvalidFsckPass)
+        );
+        assert_eq!(
+            try_parse_fstab_line("/dev/sda1 / ext4 defaults 0 200"),
+            Err(FstabErrorReason::InvalidFsckPass)
+        );
+        assert_eq!(
+            try_parse_fstab_line("/dev/sda1 /mnt/\\377 ext4"),
+            Err(FstabErrorReason::BadEscape)
+        );
+    }
+
+    #[test]
+    fn parse_fstab_line_invalid_dump() {
+        assert_eq!(
+            parse_fstab_line("/dev/sda1 / ext4 defaults x one"),
+            Some(FSTabEntry {
+                spec: "/dev/sda1".into(),
+                file: "/".into(),
+                fs_type: "ext4".into(),
+                options: "defaults".into(),
+                dump: 0,
+                fsck_pass: 0,
+            }),
+        );
+    }
+
+    #[test]
+    fn parse_fstab_strict_invalid_numbers() {
+        let fstab = "/dev/sda1 / ext4 defaults x 1
+/dev/sda2 /home ext4 defaults 0 2";
+        assert_eq!(parse_fstab(fstab.lines()).entries.len(), 2);
+        assert_eq!(
+            parse_fstab_strict(fstab.lines()),
+            Err(vec![FstabError {
+                line: 1,
+                raw: "/dev/sda1 / ext4 defaults x 1".to_string(),
+                reason: FstabErrorReason::InvalidDump,
+            }]),
+        );
+
+        let error = |line: &str| parse_fstab_strict(line.lines()).unwrap_err()[0].to_string();
+        assert_eq!(
+            error("/dev/sda1 / ext4 defaults x 1"),
+            "line 1: dump field is not a number between -128 and 127: \"/dev/sda1 / ext4 defaults x 1\"",
+        );
+        assert_eq!(
+            error("/dev/sda1 / ext4 defaults 0 200"),
+            "line 1: fsck pass field is not a number between -128 and 127: \"/dev/sda1 / ext4 defaults 0 200\"",
+        );
+    }
+
+    #[test]
+    fn parse_fstab_strict_valid() {
+        let fstab = "
+            # Filesystems.
+            /dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc / ext4 defaults 0 1
+
+            /dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e none swap
+        ";
+        assert_eq!(
+            parse_fstab_strict(fstab.lines()),
+            Ok(parse_fstab(fstab.lines())),
+        );
+    }
+
+    #[test]
+    fn parse_fstab_strict_utillinux_fstab_broken() {
+        // This fstab example is from util-linux 2.32.1's
+        // tests/ts/libmount/files/fstab.broken
+        assert_eq!(
+            parse_fstab_strict("
+bug
+UUID=d3a8f783-df75-4dc8-9163-975a891052c0 /     ext3    noatime,defaults 1 1
+UUID=fef7ccb3-821c-4de8-88dc-71472be5946f /boot ext3    noatime,defaults 1 2
+ UUID=1f2aa318-9c34-462e-8d29-260819ffd657 swap  swap    defaults        0 0
+tmpfs                   /dev/shm                tmpfs   defaults        0 0
+devpts                  /dev/pts                devpts  gid=5,mode=620
+  sysfs                   /sys                    sysfs   defaults        0 0
+this is broken line with unexpected number of fields
+proc                    /proc                   proc    defaults        0 0
+# this is comment
+/dev/mapper/foo		/home/foo              ext4	noatime,defaults 1
+
+foo.com:/mnt/share	/mnt/remote		nfs	noauto
+//bar.com/gogogo        /mnt/gogogo             cifs    user=SRGROUP/baby,noauto
+            ".lines()),
+            Err(vec![
+                FstabError {
+                    line: 2,
+                    raw: "bug".to_string(),
+                    reason: FstabErrorReason::TooFewFields,
+                },
+                FstabError {
+                    line: 9,
+                    raw: "this is broken line with unexpected number of fields".to_string(),
+                    reason: FstabErrorReason::TooManyFields,
+                },
+            ]),
+        );
+    }
+
+    #[test]
+    fn fstab_error_display() {
+        assert_eq!(
+            FstabError {
+                line: 2,
+                raw: "bug".to_string(),
+                reason: FstabErrorReason::TooFewFields,
+            }.to_string(),
+            "line 2: too few fields: \"bug\""
+        );
+    }
+
     #[test]
     fn parse_fstab_utillinux_fstab() {
         // This fstab example is from util-linux 2.32.1's
@@ -484,90 +833,90 @@ foo.com:/mnt/share	/mnt/remote		nfs	noauto
             FSTabFile {
                 entries: vec![
                     FSTabEntry {
-                        spec: "UUID=d3a8f783-df75-4dc8-9163-975a891052c0",
-                        file: "/",
-                        fs_type: "ext3",
-                        options: "noatime,defaults",
+                        spec: "UUID=d3a8f783-df75-4dc8-9163-975a891052c0".into(),
+                        file: "/".into(),
+                        fs_type: "ext3".into(),
+                        options: "noatime,defaults".into(),
                         dump: 1,
                         fsck_pass: 1
                     },
                     FSTabEntry {
-                        spec: "UUID=fef7ccb3-821c-4de8-88dc-71472be5946f",
-                        file: "/boot",
-                        fs_type: "ext3",
-                        options: "noatime,defaults",
+                        spec: "UUID=fef7ccb3-821c-4de8-88dc-71472be5946f".into(),
+                        file: "/boot".into(),
+                        fs_type: "ext3".into(),
+                        options: "noatime,defaults".into(),
                         dump: 1,
                         fsck_pass: 2
                     },
                     FSTabEntry {
-                        spec: "UUID=1f2aa318-9c34-462e-8d29-260819ffd657",
-                        file: "swap",
-                        fs_type: "swap",
-                        options: "defaults",
+                        spec: "UUID=1f2aa318-9c34-462e-8d29-260819ffd657".into(),
+                        file: "swap".into(),
+                        fs_type: "swap".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "tmpfs",
-                        file: "/dev/shm",
-                        fs_type: "tmpfs",
-                        options: "defaults",
+                        spec: "tmpfs".into(),
+                        file: "/dev/shm".into(),
+                        fs_type: "tmpfs".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "devpts",
-                        file: "/dev/pts",
-                        fs_type: "devpts",
-                        options: "gid=5,mode=620",
+                        spec: "devpts".into(),
+                        file: "/dev/pts".into(),
+                        fs_type: "devpts".into(),
+                        options: "gid=5,mode=620".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "sysfs",
-                        file: "/sys",
-                        fs_type: "sysfs",
-                        options: "defaults",
+                        spec: "sysfs".into(),
+                        file: "/sys".into(),
+                        fs_type: "sysfs".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "proc",
-                        file: "/proc",
-                        fs_type: "proc",
-                        options: "defaults",
+                        spec: "proc".into(),
+                        file: "/proc".into(),
+                        fs_type: "proc".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "/dev/mapper/foo",
-                        file: "/home/foo",
-                        fs_type: "ext4",
-                        options: "noatime,defaults",
+                        spec: "/dev/mapper/foo".into(),
+                        file: "/home/foo".into(),
+                        fs_type: "ext4".into(),
+                        options: "noatime,defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "foo.com:/mnt/share",
-                        file: "/mnt/remote",
-                        fs_type: "nfs",
-                        options: "noauto",
+                        spec: "foo.com:/mnt/share".into(),
+                        file: "/mnt/remote".into(),
+                        fs_type: "nfs".into(),
+                        options: "noauto".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "//bar.com/gogogo",
-                        file: "/mnt/gogogo",
-                        fs_type: "cifs",
-                        options: "user=SRGROUP/baby,noauto",
+                        spec: "//bar.com/gogogo".into(),
+                        file: "/mnt/gogogo".into(),
+                        fs_type: "cifs".into(),
+                        options: "user=SRGROUP/baby,noauto".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
                     FSTabEntry {
-                        spec: "/dev/foo",
-                        file: "/any/foo/",
-                        fs_type: "auto",
-                        options: "defaults",
+                        spec: "/dev/foo".into(),
+                        file: "/any/foo/".into(),
+                        fs_type: "auto".into(),
+                        options: "defaults".into(),
                         dump: 0,
                         fsck_pass: 0
                     },
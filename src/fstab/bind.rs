@@ -0,0 +1,184 @@
+//! Mounts whose real sources are other directories: bind mounts and
+//! overlayfs.
+//!
+//! These sources are hidden in `spec` or in the options, but they are
+//! dependencies just like a device is.
+
+use fstab::FSTabEntry;
+
+/// The directories an overlay mount is made of
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OverlayDirs {
+    /// `lowerdir=`, the read-only layers from top to bottom. Later
+    /// `lowerdir+=` options are appended.
+    pub lower: Vec<String>,
+
+    /// `upperdir=`, the writable layer.
+    pub upper: Option<String>,
+
+    /// `workdir=`, scratch space on the same filesystem as `upper`.
+    pub work: Option<String>,
+}
+
+/// Split an overlayfs directory list on `:`. As in the kernel's
+/// `ovl_split_lowerdirs`, a backslash escapes the next character, so
+/// `\:` is a colon inside a path.
+pub fn split_overlay_dirs(dirs: &str) -> Vec<String> {
+    split_escaped(dirs, true)
+}
+
+/// Remove the escaping from a single overlayfs directory.
+fn unescape_overlay_dir(dir: &str) -> String {
+    split_escaped(dir, false).remove(0)
+}
+
+fn split_escaped(dirs: &str, split_on_colon: bool) -> Vec<String> {
+    let mut split = vec![];
+    let mut current = String::new();
+    let mut chars = dirs.chars();
+    while let Some(chr) = chars.next() {
+        match chr {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            ':' if split_on_colon => split.push(::std::mem::take(&mut current)),
+            _ => current.push(chr),
+        }
+    }
+    split.push(current);
+    split
+}
+
+impl<'a> FSTabEntry<'a> {
+    /// Is this a `bind` or `rbind` mount?
+    pub fn is_bind(&self) -> bool {
+        self.options.has("bind") || self.options.has("rbind")
+    }
+
+    /// Is this an `rbind` mount, which also binds everything mounted
+    /// below its source?
+    pub fn is_recursive_bind(&self) -> bool {
+        self.options.has("rbind")
+    }
+
+    /// The directory a bind mount makes visible at its mount point.
+    /// None if this isn't a bind mount of an absolute path.
+    pub fn bind_source(&self) -> Option<&str> {
+        if self.is_bind() && self.spec.starts_with('/') {
+            Some(&self.spec)
+        } else {
+            None
+        }
+    }
+
+    /// The layers of an overlay mount. None if this isn't overlayfs.
+    pub fn overlay_dirs(&self) -> Option<OverlayDirs> {
+        if self.fs_type != "overlay" {
+            return None;
+        }
+
+        let mut dirs = OverlayDirs::default();
+        for option in &self.options {
+            let value = match option.unquoted_value() {
+                Some(value) => value,
+                None => continue,
+            };
+            match &option.name[..] {
+                "lowerdir" => dirs.lower = split_overlay_dirs(value),
+                "lowerdir+" => dirs.lower.push(unescape_overlay_dir(value)),
+                "upperdir" => dirs.upper = Some(unescape_overlay_dir(value)),
+                "workdir" => dirs.work = Some(unescape_overlay_dir(value)),
+                _ => {}
+            }
+        }
+        Some(dirs)
+    }
+
+    /// Every directory on another mount this entry reads from: a bind
+    /// mount's source, or an overlay's layers.
+    pub fn source_directories(&self) -> Vec<String> {
+        if let Some(source) = self.bind_source() {
+            return vec![source.to_string()];
+        }
+
+        match self.overlay_dirs() {
+            Some(dirs) => dirs.lower.into_iter().chain(dirs.upper).chain(dirs.work).collect(),
+            None => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::parse_fstab_line;
+
+    #[test]
+    fn bind() {
+        let entry = parse_fstab_line("/data/x /srv/x none bind").unwrap();
+        assert!(entry.is_bind());
+        assert!(!entry.is_recursive_bind());
+        assert_eq!(entry.bind_source(), Some("/data/x"));
+        assert_eq!(entry.source_directories(), vec!["/data/x"]);
+    }
+
+    #[test]
+    fn rbind() {
+        let entry = parse_fstab_line("/data/My\\040Dir /srv/x none rbind,ro").unwrap();
+        assert!(entry.is_bind());
+        assert!(entry.is_recursive_bind());
+        assert_eq!(entry.bind_source(), Some("/data/My Dir"));
+    }
+
+    #[test]
+    fn not_bind() {
+        let entry = parse_fstab_line("/dev/sda1 / ext4 defaults").unwrap();
+        assert!(!entry.is_bind());
+        assert_eq!(entry.bind_source(), None);
+        assert_eq!(entry.overlay_dirs(), None);
+        assert!(entry.source_directories().is_empty());
+    }
+
+    #[test]
+    fn overlay() {
+        let entry = parse_fstab_line(
+            "overlay /merged overlay lowerdir=/a:/b,upperdir=/u,workdir=/w"
+        ).unwrap();
+        assert_eq!(
+            entry.overlay_dirs(),
+            Some(OverlayDirs {
+                lower: vec!["/a".to_string(), "/b".to_string()],
+                upper: Some("/u".to_string()),
+                work: Some("/w".to_string()),
+            })
+        );
+        assert_eq!(entry.source_directories(), vec!["/a", "/b", "/u", "/w"]);
+    }
+
+    #[test]
+    fn overlay_escaped_colons() {
+        let entry = parse_fstab_line(
+            "overlay /merged overlay lowerdir=/a\\:1:/b\\\\c,upperdir=/u\\:2,workdir=/w"
+        ).unwrap();
+        let dirs = entry.overlay_dirs().unwrap();
+        assert_eq!(dirs.lower, vec!["/a:1", "/b\\c"]);
+        assert_eq!(dirs.upper, Some("/u:2".to_string()));
+    }
+
+    #[test]
+    fn overlay_read_only_and_appended_layers() {
+        let entry = parse_fstab_line(
+            "overlay /merged overlay lowerdir=/a,lowerdir+=/b\\:c"
+        ).unwrap();
+        assert_eq!(
+            entry.overlay_dirs(),
+            Some(OverlayDirs {
+                lower: vec!["/a".to_string(), "/b:c".to_string()],
+                upper: None,
+                work: None,
+            })
+        );
+    }
+}
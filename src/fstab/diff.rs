@@ -0,0 +1,238 @@
+//! Comparing the fstab of the running system with a new one.
+//!
+//! This is the fstab step of switch-to-configuration: entries are
+//! matched up by mount point, or by spec for swap, and each change is
+//! sorted by what it takes to apply it.
+
+use fstab::{FSTabEntry, FSTabFile};
+
+/// One entry present in both files, with its old and new values
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EntryChange<'a, 'b: 'a> {
+    pub old: &'a FSTabEntry<'b>,
+    pub new: &'a FSTabEntry<'b>,
+}
+
+/// The differences between two fstabs
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FstabDiff<'a, 'b: 'a> {
+    /// Entries only in the new file, in new file order.
+    pub added: Vec<&'a FSTabEntry<'b>>,
+
+    /// Entries only in the old file, in old file order.
+    pub removed: Vec<&'a FSTabEntry<'b>>,
+
+    /// Entries whose device and type are the same but whose options
+    /// differ. These can be applied with a remount.
+    pub options_changed: Vec<EntryChange<'a, 'b>>,
+
+    /// Entries whose device or filesystem type differ. These have to
+    /// be unmounted and mounted again.
+    pub device_changed: Vec<EntryChange<'a, 'b>>,
+}
+
+impl<'a, 'b> FstabDiff<'a, 'b> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.options_changed.is_empty()
+            && self.device_changed.is_empty()
+    }
+}
+
+/// What identifies an entry between two versions of a file: swap has
+/// no mount point, so it is identified by its device. A trailing slash
+/// doesn't change which mount unit an entry belongs to.
+fn key<'a>(entry: &'a FSTabEntry) -> (bool, &'a str) {
+    if entry.is_swap() {
+        (true, &entry.spec)
+    } else {
+        (false, entry.file.trim_end_matches('/'))
+    }
+}
+
+/// The entry for `key`. If there are several, the last one wins, as
+/// it would when systemd-fstab-generator writes their units.
+fn find<'a, 'b>(fstab: &'a FSTabFile<'b>, wanted: (bool, &str)) -> Option<&'a FSTabEntry<'b>> {
+    fstab.entries.iter().rev().find(|entry| key(entry) == wanted)
+}
+
+/// Is this the entry `find` would return for its key?
+fn is_last<'a, 'b>(fstab: &'a FSTabFile<'b>, entry: &'a FSTabEntry<'b>) -> bool {
+    find(fstab, key(entry)).is_some_and(|last| ::std::ptr::eq(last, entry))
+}
+
+/// Compare an old and new fstab.
+///
+/// A change to an overlay's layers counts as a device change, since
+/// overlayfs can't swap its directories on remount.
+///
+/// Changes to the dump and fsck pass fields are ignored: they don't
+/// affect a mounted filesystem. Options are compared ignoring their
+/// order and `defaults`, see `MountOptions::equivalent`.
+pub fn diff<'a, 'b>(old: &'a FSTabFile<'b>, new: &'a FSTabFile<'b>) -> FstabDiff<'a, 'b> {
+    let mut diff = FstabDiff::default();
+
+    for old_entry in old.entries.iter().filter(|entry| is_last(old, entry)) {
+        if find(new, key(old_entry)).is_none() {
+            diff.removed.push(old_entry);
+        }
+    }
+
+    for new_entry in new.entries.iter().filter(|entry| is_last(new, entry)) {
+        let old_entry = match find(old, key(new_entry)) {
+            Some(old_entry) => old_entry,
+            None => {
+                diff.added.push(new_entry);
+                continue;
+            }
+        };
+
+        let change = EntryChange {
+            old: old_entry,
+            new: new_entry,
+        };
+        if old_entry.spec != new_entry.spec
+            || old_entry.fs_type != new_entry.fs_type
+            || old_entry.overlay_dirs() != new_entry.overlay_dirs()
+        {
+            diff.device_changed.push(change);
+        } else if !old_entry.options.equivalent(&new_entry.options) {
+            diff.options_changed.push(change);
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::fixtures::MORBO;
+    use fstab::parse_fstab;
+
+    #[test]
+    fn identical() {
+        let old = parse_fstab(MORBO.lines());
+        let new = parse_fstab(MORBO.lines());
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn cosmetic_changes_are_ignored() {
+        let old = parse_fstab("/dev/sda1 / ext4 noatime,defaults 0 1".lines());
+        let new = parse_fstab("/dev/sda1\t/\text4\tdefaults,noatime 1 2".lines());
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let old = parse_fstab("
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda2 /home ext4 defaults 0 2
+/dev/sda3 none swap
+".lines());
+        let new = parse_fstab("
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda4 /srv ext4 defaults 0 2
+/dev/sda5 none swap
+".lines());
+        let result = diff(&old, &new);
+        assert_eq!(result.added, vec![&new.entries[1], &new.entries[2]]);
+        assert_eq!(result.removed, vec![&old.entries[1], &old.entries[2]]);
+        assert!(result.options_changed.is_empty());
+        assert!(result.device_changed.is_empty());
+    }
+
+    #[test]
+    fn options_changed() {
+        let old = parse_fstab("/dev/sda2 /home ext4 defaults,noatime 0 2".lines());
+        let new = parse_fstab("/dev/sda2 /home ext4 noatime,nodev 0 2".lines());
+        let result = diff(&old, &new);
+        assert_eq!(
+            result.options_changed,
+            vec![EntryChange { old: &old.entries[0], new: &new.entries[0] }]
+        );
+        assert!(result.device_changed.is_empty());
+    }
+
+    #[test]
+    fn device_and_type_changed() {
+        let old = parse_fstab("
+/dev/sda2 /home ext4 defaults 0 2
+tmpfs /tmp tmpfs mode=1777
+".lines());
+        let new = parse_fstab("
+/dev/sdb2 /home ext4 noatime 0 2
+/dev/sda3 /tmp ext4 defaults
+".lines());
+        let result = diff(&old, &new);
+        assert!(result.options_changed.is_empty());
+        assert_eq!(
+            result.device_changed,
+            vec![
+                EntryChange { old: &old.entries[0], new: &new.entries[0] },
+                EntryChange { old: &old.entries[1], new: &new.entries[1] },
+            ]
+        );
+    }
+
+    #[test]
+    fn overlay_layers_changed() {
+        let old = parse_fstab("
+overlay /merged overlay lowerdir=/a:/b,upperdir=/u,workdir=/w
+overlay /other overlay lowerdir=/a,upperdir=/u,workdir=/w
+".lines());
+        let new = parse_fstab("
+overlay /merged overlay lowerdir=/a:/c,upperdir=/u,workdir=/w
+overlay /other overlay lowerdir=/a,upperdir=/u,workdir=/w,noatime
+".lines());
+        let result = diff(&old, &new);
+        assert_eq!(
+            result.device_changed,
+            vec![EntryChange { old: &old.entries[0], new: &new.entries[0] }]
+        );
+        assert_eq!(
+            result.options_changed,
+            vec![EntryChange { old: &old.entries[1], new: &new.entries[1] }]
+        );
+    }
+
+    #[test]
+    fn swap_keyed_by_spec() {
+        let old = parse_fstab("UUID=1f2aa318 swap swap defaults 0 0".lines());
+        let new = parse_fstab("UUID=1f2aa318 none swap pri=10".lines());
+        let result = diff(&old, &new);
+        assert!(result.added.is_empty());
+        assert!(result.removed.is_empty());
+        assert_eq!(
+            result.options_changed,
+            vec![EntryChange { old: &old.entries[0], new: &new.entries[0] }]
+        );
+    }
+
+    #[test]
+    fn trailing_slash_is_the_same_mount_point() {
+        let old = parse_fstab("/dev/sda2 /home ext4 defaults".lines());
+        let new = parse_fstab("/dev/sda2 /home/ ext4 noatime".lines());
+        let result = diff(&old, &new);
+        assert!(result.added.is_empty());
+        assert!(result.removed.is_empty());
+        assert_eq!(result.options_changed.len(), 1);
+    }
+
+    #[test]
+    fn duplicate_mount_points_last_wins() {
+        let old = parse_fstab("/dev/sda2 /home ext4 defaults".lines());
+        let new = parse_fstab("
+/dev/sda2 /home ext4 defaults
+/dev/sdb2 /home ext4 defaults
+".lines());
+        let result = diff(&old, &new);
+        assert!(result.added.is_empty());
+        assert_eq!(
+            result.device_changed,
+            vec![EntryChange { old: &old.entries[0], new: &new.entries[1] }]
+        );
+    }
+}
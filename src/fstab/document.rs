@@ -0,0 +1,378 @@
+//! A lossless model of an fstab file.
+//!
+//! `parse_fstab` only keeps the entries. When editing an fstab we
+//! don't own, every comment, blank line and bit of column alignment
+//! has to survive, so `FstabDocument` keeps each line's original text
+//! and only renders the lines which were changed.
+
+use fstab::{try_parse_fstab_line, FSTabEntry, FSTabFile, FstabErrorReason, WriteOptions};
+use std::borrow::Cow;
+use std::fmt;
+
+/// One line of an fstab, without its line terminator
+#[derive(Debug, PartialEq, Clone)]
+pub enum FstabLine<'a> {
+    /// A line containing only whitespace, kept as written.
+    Blank(Cow<'a, str>),
+
+    /// A comment, including any leading whitespace and the `#`.
+    Comment(Cow<'a, str>),
+
+    /// A valid entry and its original text.
+    Entry {
+        raw: Cow<'a, str>,
+        entry: FSTabEntry<'a>,
+    },
+
+    /// A line which isn't a valid entry, and why.
+    Invalid {
+        raw: Cow<'a, str>,
+        reason: FstabErrorReason,
+    },
+}
+
+impl<'a> FstabLine<'a> {
+    /// Classify a single line of text.
+    pub fn parse(line: &'a str) -> FstabLine<'a> {
+        match try_parse_fstab_line(line) {
+            Ok(Some(entry)) => FstabLine::Entry {
+                raw: Cow::Borrowed(line),
+                entry,
+            },
+            Ok(None) if line.trim().is_empty() => FstabLine::Blank(Cow::Borrowed(line)),
+            Ok(None) => FstabLine::Comment(Cow::Borrowed(line)),
+            Err(reason) => FstabLine::Invalid {
+                raw: Cow::Borrowed(line),
+                reason,
+            },
+        }
+    }
+
+    /// The text of the line, exactly as it will be written.
+    pub fn raw(&self) -> &str {
+        match *self {
+            FstabLine::Blank(ref raw)
+            | FstabLine::Comment(ref raw)
+            | FstabLine::Entry { ref raw, .. }
+            | FstabLine::Invalid { ref raw, .. } => raw,
+        }
+    }
+
+    pub fn entry(&self) -> Option<&FSTabEntry<'a>> {
+        match *self {
+            FstabLine::Entry { ref entry, .. } => Some(entry),
+            _ => None,
+        }
+    }
+}
+
+/// An fstab file which can be edited and written back out without
+/// reformatting the lines which weren't touched.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FstabDocument<'a> {
+    lines: Vec<FstabLine<'a>>,
+
+    /// Whether the last line was terminated by a newline.
+    trailing_newline: bool,
+}
+
+impl<'a> FstabDocument<'a> {
+    /// Parse the complete text of an fstab.
+    ///
+    /// Lines are split on `\n` only, so a `\r` from a `\r\n` line
+    /// ending stays part of the line's text and is written back out.
+    pub fn parse(text: &'a str) -> FstabDocument<'a> {
+        let trailing_newline = text.ends_with('\n');
+        let body = if trailing_newline {
+            &text[..text.len() - 1]
+        } else {
+            text
+        };
+
+        let lines = if text.is_empty() {
+            vec![]
+        } else {
+            body.split('\n').map(FstabLine::parse).collect()
+        };
+
+        FstabDocument {
+            lines,
+            trailing_newline,
+        }
+    }
+
+    pub fn lines(&self) -> &[FstabLine<'a>] {
+        &self.lines
+    }
+
+    /// Iterate over the valid entries in file order.
+    pub fn entries(&self) -> impl Iterator<Item = &FSTabEntry<'a>> {
+        self.lines.iter().filter_map(FstabLine::entry)
+    }
+
+    /// The valid entries as an `FSTabFile`, as parse_fstab would have
+    /// returned them.
+    pub fn to_fstab_file(&self) -> FSTabFile<'a> {
+        FSTabFile {
+            entries: self.entries().cloned().collect(),
+        }
+    }
+
+    /// Find the first entry mounted at `mount_point`.
+    pub fn find(&self, mount_point: &str) -> Option<&FSTabEntry<'a>> {
+        self.entries().find(|entry| entry.file == mount_point)
+    }
+
+    fn position(&self, mount_point: &str) -> Option<usize> {
+        self.lines.iter().position(|line| match line.entry() {
+            Some(entry) => entry.file == mount_point,
+            None => false,
+        })
+    }
+
+    /// Add an entry after the last existing entry, or at the end of
+    /// the file if there are none. Its columns are aligned with the
+    /// entry before it where possible.
+    pub fn insert(&mut self, entry: FSTabEntry<'a>) {
+        let position = self
+            .lines
+            .iter()
+            .rposition(|line| line.entry().is_some());
+
+        let raw = render_entry(&entry, position.map(|idx| self.lines[idx].raw()));
+        let line = FstabLine::Entry {
+            raw: Cow::Owned(raw),
+            entry,
+        };
+
+        match position {
+            Some(idx) => self.lines.insert(idx + 1, line),
+            None => {
+                self.lines.push(line);
+                self.trailing_newline = true;
+            }
+        }
+    }
+
+    /// Remove the first entry mounted at `mount_point`, returning it.
+    pub fn remove(&mut self, mount_point: &str) -> Option<FSTabEntry<'a>> {
+        let idx = self.position(mount_point)?;
+        match self.lines.remove(idx) {
+            FstabLine::Entry { entry, .. } => Some(entry),
+            _ => unreachable!("position only matches entries"),
+        }
+    }
+
+    /// Replace the first entry mounted at `mount_point` in place,
+    /// returning the old entry. The new line is aligned like the line
+    /// it replaces.
+    pub fn replace(&mut self, mount_point: &str, entry: FSTabEntry<'a>) -> Option<FSTabEntry<'a>> {
+        let idx = self.position(mount_point)?;
+        let raw = render_entry(&entry, Some(self.lines[idx].raw()));
+        let old = ::std::mem::replace(
+            &mut self.lines[idx],
+            FstabLine::Entry {
+                raw: Cow::Owned(raw),
+                entry,
+            },
+        );
+
+        match old {
+            FstabLine::Entry { entry, .. } => Some(entry),
+            _ => unreachable!("position only matches entries"),
+        }
+    }
+}
+
+impl<'a> fmt::Display for FstabDocument<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, line) in self.lines.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", line.raw())?;
+        }
+        if self.trailing_newline && !self.lines.is_empty() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The character offset each field of `template` starts at, if its
+/// fields are lined up in columns with spaces. Tabs depend on the
+/// reader's tab stops, so we can't line up with them, and a template
+/// separated by single spaces has no columns to keep.
+fn column_starts(template: &str) -> Option<Vec<usize>> {
+    if template.contains('\t') {
+        return None;
+    }
+
+    let mut starts = vec![];
+    let mut padded = false;
+    let mut gap = 0;
+    for (idx, chr) in template.chars().enumerate() {
+        if chr.is_whitespace() {
+            gap += 1;
+        } else {
+            if gap > 0 || starts.is_empty() {
+                padded = padded || (gap > 1 && !starts.is_empty());
+                starts.push(idx);
+            }
+            gap = 0;
+        }
+    }
+
+    if padded {
+        Some(starts)
+    } else {
+        None
+    }
+}
+
+/// Render an entry as a new line, lining its fields up with the
+/// columns of `template` when there is one.
+fn render_entry(entry: &FSTabEntry, template: Option<&str>) -> String {
+    let fields = entry.fields(WriteOptions {
+        always_dump_pass: true,
+        align_columns: false,
+    });
+    let starts = template.and_then(column_starts).unwrap_or_default();
+
+    let mut line = String::new();
+    let mut width = 0;
+    for (idx, field) in fields.iter().enumerate() {
+        if idx > 0 {
+            let start = starts.get(idx).cloned().unwrap_or(0);
+            let padding = if start > width { start - width } else { 1 };
+            line.extend(::std::iter::repeat_n(' ', padding));
+            width += padding;
+        }
+        line.push_str(field);
+        width += field.chars().count();
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::fixtures::{ALL, MORBO, UTILLINUX_FSTAB_BROKEN};
+
+    fn entry<'a>(spec: &'a str, file: &'a str, fs_type: &'a str) -> FSTabEntry<'a> {
+        FSTabEntry {
+            spec: spec.into(),
+            file: file.into(),
+            fs_type: fs_type.into(),
+            options: "defaults".into(),
+            dump: 0,
+            fsck_pass: 2,
+        }
+    }
+
+    #[test]
+    fn round_trips_byte_identical() {
+        for text in [
+            "",
+            "\n",
+            "\n\n",
+            "   \n",
+            "no trailing newline /mnt",
+            "/dev/sda1 / ext4 defaults 0 1",
+            "/dev/sda1 / ext4 defaults 0 1\r\n# dos\r\n",
+        ].iter().chain(ALL) {
+            assert_eq!(&FstabDocument::parse(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn classifies_lines() {
+        let doc = FstabDocument::parse(UTILLINUX_FSTAB_BROKEN);
+        let lines = doc.lines();
+        assert_eq!(
+            lines[0],
+            FstabLine::Invalid {
+                raw: "bug".into(),
+                reason: FstabErrorReason::TooFewFields,
+            }
+        );
+        assert!(lines[1].entry().is_some());
+        assert_eq!(lines[9], FstabLine::Comment("# this is comment".into()));
+        assert_eq!(lines[11], FstabLine::Blank("".into()));
+    }
+
+    #[test]
+    fn entries_match_parse_fstab() {
+        for text in ALL {
+            assert_eq!(
+                FstabDocument::parse(text).to_fstab_file(),
+                ::fstab::parse_fstab(text.lines())
+            );
+        }
+    }
+
+    #[test]
+    fn remove_keeps_other_lines() {
+        let mut doc = FstabDocument::parse(MORBO);
+        let removed = doc.remove("/boot").unwrap();
+        assert_eq!(removed.spec, "/dev/disk/by-uuid/2D03-B634");
+        assert_eq!(doc.remove("/boot"), None);
+        assert_eq!(
+            doc.to_string(),
+            MORBO.replace("/dev/disk/by-uuid/2D03-B634 /boot vfat defaults 0 2\n", "")
+        );
+    }
+
+    #[test]
+    fn replace_in_place() {
+        let mut doc = FstabDocument::parse(MORBO);
+        let old = doc.replace("/boot", entry("/dev/sda1", "/boot", "vfat"));
+        assert_eq!(old.unwrap().spec, "/dev/disk/by-uuid/2D03-B634");
+        assert_eq!(
+            doc.to_string(),
+            MORBO.replace(
+                "/dev/disk/by-uuid/2D03-B634 /boot vfat defaults 0 2",
+                "/dev/sda1 /boot vfat defaults 0 2"
+            )
+        );
+        assert_eq!(doc.replace("/nope", entry("/dev/sda1", "/nope", "vfat")), None);
+    }
+
+    #[test]
+    fn insert_after_last_entry_aligned() {
+        let text = "# comment
+tmpfs                   /dev/shm                tmpfs   defaults        0 0
+
+# trailing comment
+";
+        let mut doc = FstabDocument::parse(text);
+        doc.insert(entry("/dev/sda1", "/mnt/My Disk", "ext4"));
+        assert_eq!(
+            doc.to_string(),
+            "# comment
+tmpfs                   /dev/shm                tmpfs   defaults        0 0
+/dev/sda1               /mnt/My\\040Disk         ext4    defaults        0 2
+
+# trailing comment
+"
+        );
+        assert_eq!(doc.find("/mnt/My Disk").unwrap().spec, "/dev/sda1");
+    }
+
+    #[test]
+    fn insert_into_empty() {
+        let mut doc = FstabDocument::parse("");
+        doc.insert(entry("/dev/sda1", "/", "ext4"));
+        assert_eq!(doc.to_string(), "/dev/sda1 / ext4 defaults 0 2\n");
+    }
+
+    #[test]
+    fn insert_after_tab_separated_entry() {
+        let mut doc = FstabDocument::parse("proc\t/proc\tproc\tdefaults\t0 0\n");
+        doc.insert(entry("/dev/sda1", "/", "ext4"));
+        assert_eq!(
+            doc.to_string(),
+            "proc\t/proc\tproc\tdefaults\t0 0\n/dev/sda1 / ext4 defaults 0 2\n"
+        );
+    }
+}
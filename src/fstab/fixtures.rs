@@ -0,0 +1,89 @@
+//! fstab files shared by the tests of several modules.
+
+/// A NixOS generated fstab.
+pub const MORBO: &str = "# This is a generated file.  Do not edit!
+#
+# To make changes, edit the fileSystems and swapDevices NixOS options
+# in your /etc/nixos/configuration.nix file.
+
+# Filesystems.
+/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc / ext4 defaults 0 1
+/dev/disk/by-uuid/2D03-B634 /boot vfat defaults 0 2
+
+
+# Swap devices.
+/dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e none swap
+";
+
+/// util-linux 2.32.1's tests/ts/libmount/files/fstab
+pub const UTILLINUX_FSTAB: &str = "UUID=d3a8f783-df75-4dc8-9163-975a891052c0 /     ext3    noatime,defaults 1 1
+UUID=fef7ccb3-821c-4de8-88dc-71472be5946f /boot ext3    noatime,defaults 1 2
+UUID=1f2aa318-9c34-462e-8d29-260819ffd657 swap  swap    defaults        0 0
+tmpfs                   /dev/shm                tmpfs   defaults        0 0
+devpts                  /dev/pts                devpts  gid=5,mode=620  0 0
+sysfs                   /sys                    sysfs   defaults        0 0
+proc                    /proc                   proc    defaults        0 0
+# this is comment
+/dev/mapper/foo\t\t/home/foo              ext4\tnoatime,defaults 0 0
+
+foo.com:/mnt/share\t/mnt/remote\t\tnfs\tnoauto
+//bar.com/gogogo        /mnt/gogogo             cifs    user=SRGROUP/baby,noauto
+
+/dev/foo\t\t/any/foo/\t\tauto\tdefaults 0 0
+";
+
+/// util-linux 2.32.1's tests/ts/libmount/files/fstab.comment
+pub const UTILLINUX_FSTAB_COMMENT: &str = "#
+ # this is a leading comment
+#
+
+# this comments belongs to the first fs
+UUID=d3a8f783-df75-4dc8-9163-975a891052c0 /     ext3    noatime,defaults 1 1
+UUID=fef7ccb3-821c-4de8-88dc-71472be5946f /boot ext3    noatime,defaults 1 2
+
+# 3rd fs comment + newline padding
+
+UUID=1f2aa318-9c34-462e-8d29-260819ffd657 swap  swap    defaults        0 0
+tmpfs                   /dev/shm                tmpfs   defaults        0 0
+devpts                  /dev/pts                devpts  gid=5,mode=620  0 0
+sysfs                   /sys                    sysfs   defaults        0 0
+proc                    /proc                   proc    defaults        0 0
+# this is comment
+/dev/mapper/foo\t\t/home/foo              ext4\tnoatime,defaults 0 0
+foo.com:/mnt/share\t/mnt/remote\t\tnfs\tnoauto
+//bar.com/gogogo        /mnt/gogogo             cifs    user=SRGROUP/baby,noauto
+/dev/foo\t\t/any/foo/\t\tauto\tdefaults 0 0
+
+#this is a trailing comment
+";
+
+/// util-linux 2.32.1's tests/ts/libmount/files/fstab.broken
+pub const UTILLINUX_FSTAB_BROKEN: &str = "bug
+UUID=d3a8f783-df75-4dc8-9163-975a891052c0 /     ext3    noatime,defaults 1 1
+UUID=fef7ccb3-821c-4de8-88dc-71472be5946f /boot ext3    noatime,defaults 1 2
+ UUID=1f2aa318-9c34-462e-8d29-260819ffd657 swap  swap    defaults        0 0
+tmpfs                   /dev/shm                tmpfs   defaults        0 0
+devpts                  /dev/pts                devpts  gid=5,mode=620
+  sysfs                   /sys                    sysfs   defaults        0 0
+this is broken line with unexpected number of fields
+proc                    /proc                   proc    defaults        0 0
+# this is comment
+/dev/mapper/foo\t\t/home/foo              ext4\tnoatime,defaults 1
+
+foo.com:/mnt/share\t/mnt/remote\t\tnfs\tnoauto
+//bar.com/gogogo        /mnt/gogogo             cifs    user=SRGROUP/baby,noauto
+";
+
+/// Mount points and sources which need octal escapes.
+pub const ESCAPES: &str = "LABEL=My\\040Disk /mnt/My\\040Disk\\011Tab ext4 defaults 0 2
+/dev/sda1 /mnt/back\\134slash\\012newline ext4
+";
+
+/// Every fixture, for tests which should hold for any fstab.
+pub const ALL: &[&str] = &[
+    MORBO,
+    UTILLINUX_FSTAB,
+    UTILLINUX_FSTAB_COMMENT,
+    UTILLINUX_FSTAB_BROKEN,
+    ESCAPES,
+];
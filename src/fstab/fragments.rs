@@ -0,0 +1,240 @@
+//! A main fstab plus drop-in fragments, as util-linux reads
+//! `/etc/fstab` and `/etc/fstab.d/*.fstab`.
+//!
+//! See libmount/src/tab_parse.c, `mnt_table_parse_dir`.
+
+use fstab::read::read_numbered_entries_from_path;
+use fstab::{FSTabEntry, FSTabFile};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where an entry was read from
+#[derive(Debug, PartialEq, Clone)]
+pub struct Provenance {
+    pub path: PathBuf,
+
+    /// The 1-based line number.
+    pub line: usize,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)
+    }
+}
+
+/// An entry and the file and line it came from
+#[derive(Debug, PartialEq, Clone)]
+pub struct SourcedEntry {
+    pub entry: FSTabEntry<'static>,
+    pub provenance: Provenance,
+}
+
+/// Two files which both have an entry for the same mount point
+#[derive(Debug, PartialEq, Clone)]
+pub struct Conflict {
+    pub file: String,
+    pub first: Provenance,
+    pub second: Provenance,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is mounted by both {} and {}",
+            self.file, self.first, self.second
+        )
+    }
+}
+
+/// All the entries of a main fstab and its fragments
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MergedFstab {
+    /// The main file's entries, then each fragment's in lexical order
+    /// of their file names.
+    pub entries: Vec<SourcedEntry>,
+
+    /// Mount points with entries in more than one file. Duplicates
+    /// within one file are reported by `lint` instead.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl MergedFstab {
+    /// Read `main` and then every `*.fstab` file in `fragment_dir`.
+    ///
+    /// As in util-linux, hidden files and anything which isn't a
+    /// regular file (after following symlinks) are skipped, and a
+    /// missing fragment directory is the same as an empty one. A
+    /// missing main file or an invalid line in any file is an error.
+    pub fn load(main: &Path, fragment_dir: &Path) -> io::Result<MergedFstab> {
+        let mut merged = MergedFstab::default();
+        merged.add_file(main)?;
+        for fragment in fragments(fragment_dir)? {
+            merged.add_file(&fragment)?;
+        }
+        Ok(merged)
+    }
+
+    fn add_file(&mut self, path: &Path) -> io::Result<()> {
+        for (line, entry) in read_numbered_entries_from_path(path)? {
+            let provenance = Provenance {
+                path: path.to_path_buf(),
+                line,
+            };
+
+            if !entry.is_swap() {
+                let mount_point = entry.file.trim_end_matches('/');
+                let earlier = self.entries.iter().find(|earlier| {
+                    !earlier.entry.is_swap()
+                        && earlier.entry.file.trim_end_matches('/') == mount_point
+                        && earlier.provenance.path != path
+                });
+                if let Some(earlier) = earlier {
+                    self.conflicts.push(Conflict {
+                        file: entry.file.to_string(),
+                        first: earlier.provenance.clone(),
+                        second: provenance.clone(),
+                    });
+                }
+            }
+
+            self.entries.push(SourcedEntry { entry, provenance });
+        }
+        Ok(())
+    }
+
+    /// The entry for a mount point. If there are several, the last
+    /// one wins, as in `diff`.
+    pub fn find(&self, mount_point: &str) -> Option<&SourcedEntry> {
+        let mount_point = mount_point.trim_end_matches('/');
+        self.entries.iter().rev().find(|sourced| {
+            !sourced.entry.is_swap() && sourced.entry.file.trim_end_matches('/') == mount_point
+        })
+    }
+
+    /// All the entries as one fstab, without their provenance.
+    pub fn to_fstab_file(&self) -> FSTabFile<'static> {
+        FSTabFile {
+            entries: self.entries.iter().map(|sourced| sourced.entry.clone()).collect(),
+        }
+    }
+}
+
+/// The fragment files in `dir`, sorted by name.
+fn fragments(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut paths = vec![];
+    for dir_entry in read_dir {
+        let path = dir_entry?.path();
+        let is_fragment = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => !name.starts_with('.') && name.ends_with(".fstab"),
+            None => false,
+        };
+        if is_fragment && fs::metadata(&path).map(|meta| meta.is_file()).unwrap_or(false) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::TempDir;
+
+    fn files(merged: &MergedFstab) -> Vec<(&str, String)> {
+        merged.entries.iter()
+            .map(|sourced| (&sourced.entry.file[..], sourced.provenance.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn main_then_fragments_in_lexical_order() {
+        let dir = TempDir::new();
+        let main = dir.write("fstab", "# root\n/dev/sda1 / ext4 defaults 0 1\n");
+        dir.write("fstab.d/20-data.fstab", "/dev/sdb1 /data ext4 defaults 0 2\n");
+        dir.write("fstab.d/10-boot.fstab", "\n/dev/sda2 /boot vfat defaults 0 2\n");
+        dir.write("fstab.d/.hidden.fstab", "/dev/sdc1 /hidden ext4\n");
+        dir.write("fstab.d/notes.txt", "/dev/sdd1 /txt ext4\n");
+        dir.write("fstab.d/subdir.fstab/x.fstab", "/dev/sde1 /subdir ext4\n");
+        dir.symlink("fstab.d/30-link.fstab", "20-data.fstab");
+        dir.symlink("fstab.d/40-dangling.fstab", "missing");
+
+        let merged = MergedFstab::load(&main, &dir.path().join("fstab.d")).unwrap();
+        let d = dir.path().display();
+        assert_eq!(
+            files(&merged),
+            vec![
+                ("/", format!("{}/fstab:2", d)),
+                ("/boot", format!("{}/fstab.d/10-boot.fstab:2", d)),
+                ("/data", format!("{}/fstab.d/20-data.fstab:1", d)),
+                ("/data", format!("{}/fstab.d/30-link.fstab:1", d)),
+            ]
+        );
+        assert_eq!(
+            merged.conflicts,
+            vec![Conflict {
+                file: "/data".to_string(),
+                first: merged.entries[2].provenance.clone(),
+                second: merged.entries[3].provenance.clone(),
+            }]
+        );
+        assert_eq!(merged.find("/data/"), Some(&merged.entries[3]));
+        assert_eq!(merged.to_fstab_file().entries.len(), 4);
+    }
+
+    #[test]
+    fn conflicts_only_across_files() {
+        let dir = TempDir::new();
+        let main = dir.write("fstab", "
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda2 none swap
+tmpfs /tmp tmpfs defaults
+tmpfs /tmp/ tmpfs size=1G
+");
+        dir.write("fstab.d/swap.fstab", "/dev/sdb2 none swap\n");
+        dir.write("fstab.d/tmp.fstab", "tmpfs /tmp tmpfs size=2G\n");
+
+        let merged = MergedFstab::load(&main, &dir.path().join("fstab.d")).unwrap();
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(
+            merged.conflicts[0].to_string(),
+            format!(
+                "/tmp is mounted by both {0}/fstab:4 and {0}/fstab.d/tmp.fstab:1",
+                dir.path().display()
+            )
+        );
+    }
+
+    #[test]
+    fn missing_fragment_dir() {
+        let dir = TempDir::new();
+        let main = dir.write("fstab", "/dev/sda1 / ext4 defaults 0 1\n");
+        let merged = MergedFstab::load(&main, &dir.path().join("fstab.d")).unwrap();
+        assert_eq!(merged.entries.len(), 1);
+        assert!(merged.conflicts.is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let dir = TempDir::new();
+        let fragment_dir = dir.path().join("fstab.d");
+        let missing = MergedFstab::load(&dir.path().join("fstab"), &fragment_dir).unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+
+        let main = dir.write("fstab", "/dev/sda1 / ext4 defaults 0 1\n");
+        let broken = dir.write("fstab.d/broken.fstab", "bug\n");
+        let invalid = MergedFstab::load(&main, &fragment_dir).unwrap_err();
+        assert_eq!(invalid.kind(), io::ErrorKind::InvalidData);
+        assert!(invalid.to_string().starts_with(&format!("invalid fstab {}", broken.display())));
+    }
+}
@@ -0,0 +1,232 @@
+//! Classifying entries by filesystem type.
+//!
+//! The lists follow systemd's `fstype_is_network` and
+//! `fstype_is_api_vfs` in src/basic/mountpoint-util.c, which decide
+//! how systemd orders mount units.
+
+use fstab::FSTabEntry;
+
+/// Filesystems which need the network to mount.
+pub const NETWORK_FS_TYPES: &[&str] = &[
+    "9p",
+    "afs",
+    "ceph",
+    "cifs",
+    "davfs",
+    "gfs",
+    "gfs2",
+    "glusterfs",
+    "gpfs",
+    "lustre",
+    "ncp",
+    "ncpfs",
+    "nfs",
+    "nfs4",
+    "ocfs2",
+    "pvfs2",
+    "smb3",
+    "smbfs",
+    "sshfs",
+];
+
+/// Filesystems provided by the kernel which aren't backed by any
+/// device.
+pub const PSEUDO_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "proc",
+    "pstore",
+    "ramfs",
+    "securityfs",
+    "selinuxfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+/// Local filesystems, and the other types which can appear in an
+/// fstab without being network or pseudo filesystems.
+pub const LOCAL_FS_TYPES: &[&str] = &[
+    "auto",
+    "bcachefs",
+    "btrfs",
+    "erofs",
+    "exfat",
+    "ext2",
+    "ext3",
+    "ext4",
+    "f2fs",
+    "hfsplus",
+    "iso9660",
+    "jfs",
+    "msdos",
+    "nilfs2",
+    "none",
+    "ntfs",
+    "ntfs3",
+    "overlay",
+    "reiserfs",
+    "squashfs",
+    "swap",
+    "ubifs",
+    "udf",
+    "vfat",
+    "xfs",
+    "zfs",
+];
+
+/// The filesystem type without a `fuse.` prefix, so `fuse.sshfs` is
+/// classified like `sshfs`.
+fn base_fs_type(fs_type: &str) -> &str {
+    fs_type.strip_prefix("fuse.").unwrap_or(fs_type)
+}
+
+impl<'a> FSTabEntry<'a> {
+    /// Is this swap rather than a mount?
+    pub fn is_swap(&self) -> bool {
+        self.fs_type == "swap"
+    }
+
+    /// Does mounting this need the network? True for network
+    /// filesystem types, including their FUSE versions, and for
+    /// anything marked `_netdev`. These are ordered after
+    /// network-online.target.
+    pub fn is_network(&self) -> bool {
+        self.is_network_fs() || self.options.has("_netdev")
+    }
+
+    /// Is the filesystem type a network filesystem, or the FUSE
+    /// version of one? Unlike `is_network`, a local filesystem on a
+    /// network block device, like iSCSI marked `_netdev`, isn't.
+    pub fn is_network_fs(&self) -> bool {
+        NETWORK_FS_TYPES.contains(&base_fs_type(&self.fs_type))
+    }
+
+    /// Is this a kernel provided filesystem with no backing device?
+    pub fn is_pseudo(&self) -> bool {
+        PSEUDO_FS_TYPES.contains(&&*self.fs_type)
+    }
+
+    /// Is this implemented in userspace with FUSE?
+    pub fn is_fuse(&self) -> bool {
+        self.fs_type == "fuse" || self.fs_type == "fuseblk" || self.fs_type.starts_with("fuse.")
+    }
+
+    /// Is this backed by a block device, which systemd will have a
+    /// `.device` unit for?
+    pub fn is_block_backed(&self) -> bool {
+        !self.is_pseudo() && !self.is_network_fs() && self.source().device_path().is_some()
+    }
+
+    /// Is the filesystem type one of the known network, pseudo, local
+    /// or FUSE types?
+    pub fn has_known_fs_type(&self) -> bool {
+        self.is_fuse()
+            || NETWORK_FS_TYPES.contains(&&*self.fs_type)
+            || PSEUDO_FS_TYPES.contains(&&*self.fs_type)
+            || LOCAL_FS_TYPES.contains(&&*self.fs_type)
+    }
+
+    /// Should fsck(8) check this filesystem at boot? Only block backed
+    /// filesystems with a non-zero fsck pass can be checked.
+    pub fn needs_fsck(&self) -> bool {
+        self.fsck_pass > 0 && !self.is_swap() && self.is_block_backed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fstab::parse_fstab_line;
+
+    #[test]
+    fn swap() {
+        assert!(parse_fstab_line("/dev/sda2 none swap").unwrap().is_swap());
+        assert!(!parse_fstab_line("/dev/sda1 / ext4").unwrap().is_swap());
+    }
+
+    #[test]
+    fn network() {
+        for line in &[
+            "foo.com:/mnt/share /mnt/remote nfs noauto",
+            "foo.com:/mnt/share /mnt/remote nfs4",
+            "//bar.com/gogogo /mnt/gogogo cifs user=SRGROUP/baby,noauto",
+            "//bar.com/gogogo /mnt/gogogo smb3",
+            "user@host:/home /mnt/home fuse.sshfs",
+            "server:/vol /mnt/gluster glusterfs",
+            "mon1:/ /mnt/ceph ceph",
+            "host0 /mnt/9p 9p trans=virtio",
+            "/dev/sdb1 /mnt/iscsi ext4 _netdev",
+        ] {
+            assert!(parse_fstab_line(line).unwrap().is_network(), "{}", line);
+        }
+        assert!(!parse_fstab_line("/dev/sda1 / ext4").unwrap().is_network());
+        assert!(!parse_fstab_line("tmpfs /tmp tmpfs").unwrap().is_network());
+
+        let iscsi = parse_fstab_line("/dev/sdb1 /mnt/iscsi ext4 _netdev").unwrap();
+        assert!(!iscsi.is_network_fs());
+        assert!(parse_fstab_line("user@host:/home /mnt/home fuse.sshfs").unwrap().is_network_fs());
+    }
+
+    #[test]
+    fn pseudo() {
+        for line in &[
+            "proc /proc proc defaults",
+            "sysfs /sys sysfs defaults",
+            "tmpfs /dev/shm tmpfs defaults",
+            "devpts /dev/pts devpts gid=5,mode=620",
+            "cgroup2 /sys/fs/cgroup cgroup2",
+        ] {
+            assert!(parse_fstab_line(line).unwrap().is_pseudo(), "{}", line);
+        }
+        assert!(!parse_fstab_line("/dev/sda1 / ext4").unwrap().is_pseudo());
+    }
+
+    #[test]
+    fn fuse() {
+        assert!(parse_fstab_line("user@host:/ /mnt fuse.sshfs").unwrap().is_fuse());
+        assert!(parse_fstab_line("/dev/sdb1 /mnt fuseblk").unwrap().is_fuse());
+        assert!(parse_fstab_line("bindfs#/a /mnt fuse").unwrap().is_fuse());
+        assert!(!parse_fstab_line("/dev/sda1 / ext4").unwrap().is_fuse());
+    }
+
+    #[test]
+    fn block_backed() {
+        assert!(parse_fstab_line("UUID=d3a8f783 / ext3").unwrap().is_block_backed());
+        assert!(parse_fstab_line("/dev/mapper/foo /home/foo ext4").unwrap().is_block_backed());
+        assert!(parse_fstab_line("/dev/sda2 none swap").unwrap().is_block_backed());
+        assert!(!parse_fstab_line("tmpfs /tmp tmpfs").unwrap().is_block_backed());
+        assert!(!parse_fstab_line("/data/x /srv/x none bind").unwrap().is_block_backed());
+        assert!(parse_fstab_line("/dev/sdb1 /mnt ext4 _netdev").unwrap().is_block_backed());
+        assert!(!parse_fstab_line("foo.com:/mnt/share /mnt nfs").unwrap().is_block_backed());
+    }
+
+    #[test]
+    fn known_fs_types() {
+        assert!(parse_fstab_line("/dev/sda1 / ext4").unwrap().has_known_fs_type());
+        assert!(parse_fstab_line("tmpfs /tmp tmpfs").unwrap().has_known_fs_type());
+        assert!(parse_fstab_line("foo:/ /mnt nfs").unwrap().has_known_fs_type());
+        assert!(parse_fstab_line("foo:/ /mnt fuse.whatever").unwrap().has_known_fs_type());
+        assert!(!parse_fstab_line("/dev/sda1 / ext5").unwrap().has_known_fs_type());
+    }
+
+    #[test]
+    fn needs_fsck() {
+        assert!(parse_fstab_line("UUID=d3a8f783 / ext3 defaults 1 1").unwrap().needs_fsck());
+        assert!(!parse_fstab_line("UUID=d3a8f783 / ext3 defaults 1 0").unwrap().needs_fsck());
+        assert!(!parse_fstab_line("foo.com:/mnt/share /mnt nfs defaults 0 2").unwrap().needs_fsck());
+        assert!(parse_fstab_line("/dev/sdb1 /mnt/iscsi ext4 _netdev 0 2").unwrap().needs_fsck());
+        assert!(!parse_fstab_line("tmpfs /tmp tmpfs defaults 0 2").unwrap().needs_fsck());
+        assert!(!parse_fstab_line("/dev/sda2 none swap defaults 0 2").unwrap().needs_fsck());
+    }
+}
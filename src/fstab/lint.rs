@@ -0,0 +1,338 @@
+//! Warnings about fstab entries which parse but are probably wrong.
+//!
+//! This is meant to run against generated configurations before they
+//! are deployed, so it errs on the side of warning.
+
+use fstab::{parse_fstab, parse_fstab_line, path_is_under, FSTabEntry, FSTabFile};
+use std::fmt;
+
+/// What is suspicious about an entry
+#[derive(Debug, PartialEq, Clone)]
+pub enum LintKind {
+    /// Another entry earlier in the file has the same mount point.
+    /// Only one of them can be mounted.
+    DuplicateMountPoint { first: usize },
+
+    /// The mount point isn't an absolute path.
+    RelativeMountPoint,
+
+    /// An fsck pass is set on a network or pseudo filesystem, which
+    /// fsck can't check.
+    FsckPassNotCheckable,
+
+    /// The root filesystem is block backed but its fsck pass isn't 1.
+    /// fstab(5) says root should be checked first.
+    RootFsckPassNotOne,
+
+    /// A swap entry's mount point isn't `none` or `swap`.
+    SwapMountPoint,
+
+    /// The entry is mounted under a `noauto` entry, but isn't `noauto`
+    /// itself, so mounting it depends on a mount which won't happen.
+    NestedUnderNoauto { parent: usize },
+
+    /// The filesystem type isn't one we know about.
+    UnknownFsType,
+
+    /// The dump field is neither 0 nor 1.
+    DumpOutOfRange,
+
+    /// The fsck pass is neither 0, 1 nor 2.
+    FsckPassOutOfRange,
+}
+
+/// A warning about a specific entry
+#[derive(Debug, PartialEq, Clone)]
+pub struct LintWarning {
+    /// The index of the entry in `FSTabFile::entries`.
+    pub entry: usize,
+
+    /// The entry's mount point, to make the warning readable on its
+    /// own.
+    pub file: String,
+
+    pub kind: LintKind,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "entry {} ({}): ", self.entry + 1, self.file)?;
+        match self.kind {
+            LintKind::DuplicateMountPoint { first } => {
+                write!(f, "duplicate mount point, first used by entry {}", first + 1)
+            }
+            LintKind::RelativeMountPoint => write!(f, "mount point is not an absolute path"),
+            LintKind::FsckPassNotCheckable => {
+                write!(f, "fsck pass is set, but network and pseudo filesystems can't be checked")
+            }
+            LintKind::RootFsckPassNotOne => write!(f, "the root filesystem should have fsck pass 1"),
+            LintKind::SwapMountPoint => write!(f, "swap should have a mount point of none"),
+            LintKind::NestedUnderNoauto { parent } => write!(
+                f,
+                "mounted under entry {}, which is noauto, but is not noauto itself",
+                parent + 1
+            ),
+            LintKind::UnknownFsType => write!(f, "unknown filesystem type"),
+            LintKind::DumpOutOfRange => write!(f, "dump should be 0 or 1"),
+            LintKind::FsckPassOutOfRange => write!(f, "fsck pass should be 0, 1 or 2"),
+        }
+    }
+}
+
+fn is_noauto(entry: &FSTabEntry) -> bool {
+    entry.options.has("noauto")
+}
+
+/// Is a dump or fsck pass field a number which doesn't fit in an i8?
+/// `parse_fstab` reads these as 0.
+fn overflows(field: Option<&&str>) -> bool {
+    field.is_some_and(|field| {
+        let digits = field.strip_prefix('-').or_else(|| field.strip_prefix('+')).unwrap_or(field);
+        !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) && field.parse::<i8>().is_err()
+    })
+}
+
+/// Check every entry, returning warnings ordered by entry.
+///
+/// Dump and fsck pass fields too big to parse were read as 0, so
+/// aren't reported here. Use `lint_lines` to catch those too.
+pub fn lint(fstab: &FSTabFile) -> Vec<LintWarning> {
+    check(fstab, &[])
+}
+
+/// Parse the lines of an fstab as `parse_fstab` does and check every
+/// entry, going back to the lines for the dump and fsck pass fields.
+pub fn lint_lines<'a, T: Iterator<Item = &'a str>>(fstab_lines: T) -> Vec<LintWarning> {
+    let lines = fstab_lines
+        .filter(|line| parse_fstab_line(line).is_some())
+        .collect::<Vec<_>>();
+    check(&parse_fstab(lines.iter().cloned()), &lines)
+}
+
+/// Check the entries of `fstab`, with the line each was parsed from
+/// if `lines` has them.
+fn check(fstab: &FSTabFile, lines: &[&str]) -> Vec<LintWarning> {
+    let mut warnings = vec![];
+
+    for (idx, entry) in fstab.entries.iter().enumerate() {
+        let fields = lines.get(idx).map(|line| line.split_whitespace().collect::<Vec<_>>()).unwrap_or_default();
+        let dump_out_of_range = overflows(fields.get(4)) || (entry.dump != 0 && entry.dump != 1);
+        let pass_out_of_range = overflows(fields.get(5)) || entry.fsck_pass < 0 || entry.fsck_pass > 2;
+
+        let mut warn = |kind| {
+            warnings.push(LintWarning {
+                entry: idx,
+                file: entry.file.to_string(),
+                kind,
+            })
+        };
+
+        if entry.is_swap() {
+            if entry.file != "none" && entry.file != "swap" {
+                warn(LintKind::SwapMountPoint);
+            }
+        } else {
+            if !entry.file.starts_with('/') {
+                warn(LintKind::RelativeMountPoint);
+            }
+
+            let first = fstab.entries[..idx].iter().position(|earlier| {
+                !earlier.is_swap()
+                    && earlier.file.trim_end_matches('/') == entry.file.trim_end_matches('/')
+            });
+            if let Some(first) = first {
+                warn(LintKind::DuplicateMountPoint { first });
+            }
+
+            if !is_noauto(entry) {
+                let parent = fstab.entries.iter().position(|parent| {
+                    !parent.is_swap() && is_noauto(parent) && path_is_under(&entry.file, &parent.file)
+                });
+                if let Some(parent) = parent {
+                    warn(LintKind::NestedUnderNoauto { parent });
+                }
+            }
+
+            if entry.file.trim_end_matches('/').is_empty()
+                && entry.is_block_backed()
+                && entry.fsck_pass != 1
+                && !pass_out_of_range
+            {
+                warn(LintKind::RootFsckPassNotOne);
+            }
+        }
+
+        if entry.fsck_pass > 0 && (entry.is_network_fs() || entry.is_pseudo()) {
+            warn(LintKind::FsckPassNotCheckable);
+        }
+
+        if !entry.has_known_fs_type() {
+            warn(LintKind::UnknownFsType);
+        }
+
+        if dump_out_of_range {
+            warn(LintKind::DumpOutOfRange);
+        }
+
+        if pass_out_of_range {
+            warn(LintKind::FsckPassOutOfRange);
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::fixtures::{MORBO, UTILLINUX_FSTAB};
+    use fstab::parse_fstab;
+
+    fn kinds(fstab: &str) -> Vec<(usize, LintKind)> {
+        lint_lines(fstab.lines())
+            .into_iter()
+            .map(|warning| (warning.entry, warning.kind))
+            .collect()
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(kinds(MORBO), vec![]);
+    }
+
+    #[test]
+    fn utillinux_fstab() {
+        // This uses "swap" as the swap mount point, which is accepted.
+        assert_eq!(kinds(UTILLINUX_FSTAB), vec![]);
+    }
+
+    #[test]
+    fn duplicate_mount_points() {
+        assert_eq!(
+            kinds("
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda2 /home ext4 defaults 0 2
+/dev/sda3 /home/ ext4 defaults 0 2
+/dev/sda4 none swap
+/dev/sda5 none swap
+"),
+            vec![(2, LintKind::DuplicateMountPoint { first: 1 })]
+        );
+    }
+
+    #[test]
+    fn relative_mount_point() {
+        assert_eq!(
+            kinds("/dev/sda2 home ext4"),
+            vec![(0, LintKind::RelativeMountPoint)]
+        );
+    }
+
+    #[test]
+    fn fsck_on_network_and_pseudo() {
+        assert_eq!(
+            kinds("
+foo.com:/mnt/share /mnt/remote nfs defaults 0 2
+tmpfs /tmp tmpfs defaults 0 2
+"),
+            vec![
+                (0, LintKind::FsckPassNotCheckable),
+                (1, LintKind::FsckPassNotCheckable),
+            ]
+        );
+    }
+
+    #[test]
+    fn root_fsck_pass() {
+        assert_eq!(
+            kinds("/dev/sda1 / ext4 defaults 0 2"),
+            vec![(0, LintKind::RootFsckPassNotOne)]
+        );
+        assert_eq!(kinds("rpool/root / zfs defaults 0 0"), vec![]);
+        assert_eq!(kinds("tmpfs / tmpfs mode=755"), vec![]);
+        assert_eq!(
+            kinds("/dev/sdb1 / ext4 _netdev 0 2"),
+            vec![(0, LintKind::RootFsckPassNotOne)]
+        );
+    }
+
+    #[test]
+    fn swap_mount_point() {
+        assert_eq!(
+            kinds("/dev/sda2 /swap swap"),
+            vec![(0, LintKind::SwapMountPoint)]
+        );
+    }
+
+    #[test]
+    fn nested_under_noauto() {
+        assert_eq!(
+            kinds("
+/dev/sda1 / ext4 defaults 0 1
+/dev/sdb1 /mnt/usb vfat noauto
+/dev/sdb2 /mnt/usb/inner vfat defaults
+/dev/sdb3 /mnt/usb/manual vfat noauto
+/dev/sdb4 /mnt/usbstick vfat defaults
+"),
+            vec![(2, LintKind::NestedUnderNoauto { parent: 1 })]
+        );
+    }
+
+    #[test]
+    fn unknown_fs_type() {
+        assert_eq!(
+            kinds("/dev/sda1 /mnt ext5"),
+            vec![(0, LintKind::UnknownFsType)]
+        );
+    }
+
+    #[test]
+    fn dump_and_pass_out_of_range() {
+        assert_eq!(
+            kinds("
+/dev/sda2 /mnt/a ext4 defaults 2 0
+/dev/sda3 /mnt/b ext4 defaults -1 -1
+/dev/sda4 /mnt/c ext4 defaults 0 3
+/dev/sda5 /mnt/d ext4 defaults 1 2
+"),
+            vec![
+                (0, LintKind::DumpOutOfRange),
+                (1, LintKind::DumpOutOfRange),
+                (1, LintKind::FsckPassOutOfRange),
+                (2, LintKind::FsckPassOutOfRange),
+            ]
+        );
+    }
+
+    #[test]
+    fn dump_and_pass_too_big() {
+        let fstab = "
+/dev/sda1 / ext4 defaults 0 200
+/dev/sda2 /data ext4 defaults 300 0
+/dev/sda3 /srv ext4 defaults 0 -129
+# 0 200
+";
+        assert_eq!(
+            kinds(fstab),
+            vec![
+                (0, LintKind::FsckPassOutOfRange),
+                (1, LintKind::DumpOutOfRange),
+                (2, LintKind::FsckPassOutOfRange),
+            ]
+        );
+        assert_eq!(
+            lint(&parse_fstab(fstab.lines())).into_iter().map(|warning| warning.kind).collect::<Vec<_>>(),
+            vec![LintKind::RootFsckPassNotOne]
+        );
+        assert_eq!(kinds("/dev/sda1 / ext4 defaults 0 x"), vec![(0, LintKind::RootFsckPassNotOne)]);
+    }
+
+    #[test]
+    fn display() {
+        let warnings = lint(&parse_fstab("/dev/sda2 /home ext4\n/dev/sda3 /home ext4".lines()));
+        assert_eq!(
+            warnings[0].to_string(),
+            "entry 2 (/home): duplicate mount point, first used by entry 1"
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! The fourth fstab field: a comma separated list of mount options.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::iter::FromIterator;
+use std::slice;
+
+/// A single mount option, ie: `noauto` or `gid=5`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MountOption<'a> {
+    /// Everything before the first `=`.
+    pub name: Cow<'a, str>,
+
+    /// Everything after the first `=`, exactly as written including
+    /// any surrounding quotes. None if there was no `=`.
+    pub value: Option<Cow<'a, str>>,
+}
+
+impl<'a> MountOption<'a> {
+    /// Parse a single option. The input must already be split from
+    /// its neighbours.
+    pub fn parse(option: &'a str) -> MountOption<'a> {
+        match option.find('=') {
+            Some(idx) => MountOption {
+                name: Cow::Borrowed(&option[..idx]),
+                value: Some(Cow::Borrowed(&option[idx + 1..])),
+            },
+            None => MountOption {
+                name: Cow::Borrowed(option),
+                value: None,
+            },
+        }
+    }
+
+    /// Copy any borrowed strings so the option no longer borrows the
+    /// text it was parsed from.
+    pub fn into_owned(self) -> MountOption<'static> {
+        MountOption {
+            name: Cow::Owned(self.name.into_owned()),
+            value: self.value.map(|value| Cow::Owned(value.into_owned())),
+        }
+    }
+
+    /// The value with a surrounding pair of double quotes removed, as
+    /// used by SELinux contexts: `context="system_u:object_r:tmp_t:s0:c127,c456"`
+    pub fn unquoted_value(&self) -> Option<&str> {
+        self.value.as_ref().map(|value| {
+            if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                &value[1..value.len() - 1]
+            } else {
+                &value[..]
+            }
+        })
+    }
+}
+
+impl<'a> fmt::Display for MountOption<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Some(ref value) => write!(f, "{}={}", self.name, value),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// An ordered list of mount options.
+///
+/// Formatting a parsed list with `Display` reproduces the input
+/// byte-for-byte, including duplicate and empty options.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MountOptions<'a> {
+    options: Vec<MountOption<'a>>,
+}
+
+impl<'a> MountOptions<'a> {
+    /// Split an options field on commas.
+    ///
+    /// Commas inside double quotes don't split, matching libmount's
+    /// handling of SELinux contexts (see libmount/src/optstr.c). An
+    /// empty string is an empty list.
+    pub fn parse(options: &'a str) -> MountOptions<'a> {
+        if options.is_empty() {
+            return MountOptions::default();
+        }
+
+        let mut parsed = vec![];
+        let mut start = 0;
+        let mut quoted = false;
+        for (idx, chr) in options.char_indices() {
+            match chr {
+                '"' => quoted = !quoted,
+                ',' if !quoted => {
+                    parsed.push(MountOption::parse(&options[start..idx]));
+                    start = idx + 1;
+                }
+                _ => {}
+            }
+        }
+        parsed.push(MountOption::parse(&options[start..]));
+
+        MountOptions { options: parsed }
+    }
+
+    /// Copy any borrowed strings so the options no longer borrow the
+    /// text they were parsed from.
+    pub fn into_owned(self) -> MountOptions<'static> {
+        MountOptions {
+            options: self.options.into_iter().map(MountOption::into_owned).collect(),
+        }
+    }
+
+    /// Iterate over the options in the order they were written.
+    pub fn iter(&self) -> slice::Iter<'_, MountOption<'a>> {
+        self.options.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.options.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    /// Find the first option with this name.
+    pub fn find(&self, name: &str) -> Option<&MountOption<'a>> {
+        self.options.iter().find(|option| option.name == name)
+    }
+
+    /// Is the option present, with or without a value?
+    pub fn has(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    /// The unquoted value of the first option with this name. None if
+    /// the option is missing or has no value. Like libmount, the first
+    /// occurrence wins.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.find(name).and_then(|option| option.unquoted_value())
+    }
+
+    /// Do both lists have the same effect? Order doesn't matter, and
+    /// empty options and `defaults` are ignored since `defaults` only
+    /// stands in for the kernel's defaults.
+    pub fn equivalent(&self, other: &MountOptions) -> bool {
+        fn normalized(options: &MountOptions) -> Vec<String> {
+            let mut normalized = options
+                .iter()
+                .filter(|option| option.name != "defaults" && !option.name.is_empty())
+                .map(|option| option.to_string())
+                .collect::<Vec<String>>();
+            normalized.sort();
+            normalized
+        }
+
+        normalized(self) == normalized(other)
+    }
+}
+
+impl<'a> From<&'a str> for MountOptions<'a> {
+    fn from(options: &'a str) -> MountOptions<'a> {
+        MountOptions::parse(options)
+    }
+}
+
+impl<'a> fmt::Display for MountOptions<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, option) in self.options.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", option)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> FromIterator<MountOption<'a>> for MountOptions<'a> {
+    fn from_iter<T: IntoIterator<Item = MountOption<'a>>>(options: T) -> MountOptions<'a> {
+        MountOptions {
+            options: options.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b MountOptions<'a> {
+    type Item = &'b MountOption<'a>;
+    type IntoIter = slice::Iter<'b, MountOption<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.options.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names<'a>(options: &'a MountOptions) -> Vec<&'a str> {
+        options.iter().map(|option| &option.name[..]).collect()
+    }
+
+    #[test]
+    fn parse_empty() {
+        let options = MountOptions::parse("");
+        assert!(options.is_empty());
+        assert_eq!(options.to_string(), "");
+    }
+
+    #[test]
+    fn parse_simple() {
+        let options = MountOptions::parse("noatime,defaults");
+        assert_eq!(names(&options), vec!["noatime", "defaults"]);
+        assert!(options.has("noatime"));
+        assert!(!options.has("noauto"));
+        assert_eq!(options.get("noatime"), None);
+    }
+
+    #[test]
+    fn parse_values() {
+        let options = MountOptions::parse("gid=5,mode=620");
+        assert_eq!(options.get("gid"), Some("5"));
+        assert_eq!(options.get("mode"), Some("620"));
+        assert_eq!(options.get("uid"), None);
+    }
+
+    #[test]
+    fn parse_value_with_slash() {
+        let options = MountOptions::parse("user=SRGROUP/baby,noauto");
+        assert_eq!(names(&options), vec!["user", "noauto"]);
+        assert_eq!(options.get("user"), Some("SRGROUP/baby"));
+        assert!(options.has("noauto"));
+    }
+
+    #[test]
+    fn parse_value_with_equals() {
+        let options = MountOptions::parse("x-systemd.requires=foo=bar.service");
+        assert_eq!(options.get("x-systemd.requires"), Some("foo=bar.service"));
+    }
+
+    #[test]
+    fn parse_quoted_selinux_context() {
+        let options = MountOptions::parse(
+            "ro,context=\"system_u:object_r:tmp_t:s0:c127,c456\",noexec"
+        );
+        assert_eq!(names(&options), vec!["ro", "context", "noexec"]);
+        assert_eq!(
+            options.get("context"),
+            Some("system_u:object_r:tmp_t:s0:c127,c456")
+        );
+        assert_eq!(
+            options.find("context").unwrap().value,
+            Some("\"system_u:object_r:tmp_t:s0:c127,c456\"".into())
+        );
+    }
+
+    #[test]
+    fn get_first_occurrence() {
+        let options = MountOptions::parse("gid=5,gid=6");
+        assert_eq!(options.get("gid"), Some("5"));
+    }
+
+    #[test]
+    fn equivalent_ignores_order_and_defaults() {
+        let options = MountOptions::parse("noatime,defaults,gid=5");
+        assert!(options.equivalent(&"gid=5,noatime".into()));
+        assert!(options.equivalent(&"defaults,gid=5,,noatime".into()));
+        assert!(!options.equivalent(&"gid=6,noatime".into()));
+        assert!(!options.equivalent(&"gid=5".into()));
+        assert!(MountOptions::parse("defaults").equivalent(&"".into()));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for input in &[
+            "",
+            "defaults",
+            "noatime,defaults",
+            "user=SRGROUP/baby,noauto",
+            "context=\"a,b\",ro",
+            "a,,b",
+            ",",
+            "empty=,x",
+            "gid=5,gid=6",
+            "unterminated=\"a,b",
+        ] {
+            assert_eq!(&MountOptions::parse(input).to_string(), input);
+        }
+    }
+}
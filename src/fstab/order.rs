@@ -0,0 +1,334 @@
+//! The order entries have to be mounted and unmounted in.
+//!
+//! A mount depends on the mount its mount point is on, on the mounts
+//! holding the source of a bind mount or the layers of an overlay, and
+//! on the mounts for any `x-systemd.requires-mounts-for=` paths. Swap
+//! files depend on the mount holding the file.
+
+use fstab::{path_is_under, FSTabEntry, FSTabFile, Source};
+use std::error::Error;
+use std::fmt;
+
+/// Entries which depend on each other in a loop
+#[derive(Debug, PartialEq, Clone)]
+pub struct CycleError {
+    /// The mount points (or specs, for swap) of the entries in the
+    /// loop, each depending on the next and the last on the first.
+    pub cycle: Vec<String>,
+}
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mount dependency cycle: {}", self.cycle.join(" -> "))?;
+        if let Some(first) = self.cycle.first() {
+            write!(f, " -> {}", first)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for CycleError {}
+
+/// The dependencies between the entries of an fstab
+#[derive(Debug, PartialEq, Clone)]
+pub struct MountGraph<'a, 'b: 'a> {
+    fstab: &'a FSTabFile<'b>,
+
+    /// For each entry, the indexes of the entries it depends on.
+    dependencies: Vec<Vec<usize>>,
+}
+
+fn name(entry: &FSTabEntry) -> String {
+    if entry.is_swap() {
+        entry.spec.to_string()
+    } else {
+        entry.file.to_string()
+    }
+}
+
+/// The entry whose mount holds `path`: the deepest mount point which
+/// is `path` or one of its parents. Later entries hide earlier ones at
+/// the same mount point.
+fn containing(fstab: &FSTabFile, path: &str, exclude: usize) -> Option<usize> {
+    let path = path.trim_end_matches('/');
+    let mut best: Option<usize> = None;
+    for (idx, entry) in fstab.entries.iter().enumerate() {
+        if idx == exclude || entry.is_swap() {
+            continue;
+        }
+        let mount_point = entry.file.trim_end_matches('/');
+        if mount_point != path && !path_is_under(path, mount_point) {
+            continue;
+        }
+        let deeper = match best {
+            Some(best) => mount_point.len() >= fstab.entries[best].file.trim_end_matches('/').len(),
+            None => true,
+        };
+        if deeper {
+            best = Some(idx);
+        }
+    }
+    best
+}
+
+/// Paths on other mounts which an entry needs before it can be
+/// mounted.
+fn required_paths(entry: &FSTabEntry) -> Vec<String> {
+    let mut paths = vec![];
+
+    if entry.is_swap() {
+        // Swap devices aren't on any mount, but swap files are.
+        if let Source::Path(path) = entry.source() {
+            if !path.starts_with("/dev/") {
+                paths.push(path.to_string());
+            }
+        }
+        return paths;
+    }
+
+    let file = entry.file.trim_end_matches('/');
+    if let Some(parent) = file.rfind('/') {
+        let parent = &file[..parent];
+        paths.push(if parent.is_empty() { "/" } else { parent }.to_string());
+    }
+
+    paths.extend(entry.source_directories());
+
+    for option in entry.options.iter() {
+        if option.name == "x-systemd.requires-mounts-for" {
+            if let Some(value) = option.unquoted_value() {
+                paths.extend(value.split_whitespace().map(String::from));
+            }
+        }
+    }
+
+    paths
+}
+
+impl<'a, 'b> MountGraph<'a, 'b> {
+    pub fn new(fstab: &'a FSTabFile<'b>) -> MountGraph<'a, 'b> {
+        let dependencies = fstab
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                let mut dependencies = vec![];
+                let mount_point = entry.file.trim_end_matches('/');
+                for path in required_paths(entry) {
+                    // The root directory isn't a dependency of the root
+                    // mount.
+                    if !entry.is_swap() && path.trim_end_matches('/') == mount_point {
+                        continue;
+                    }
+                    if let Some(dependency) = containing(fstab, &path, idx) {
+                        if !dependencies.contains(&dependency) {
+                            dependencies.push(dependency);
+                        }
+                    }
+                }
+                dependencies.sort();
+                dependencies
+            })
+            .collect();
+
+        MountGraph {
+            fstab,
+            dependencies,
+        }
+    }
+
+    /// The indexes of the entries `entry` depends on, in file order.
+    pub fn dependencies(&self, entry: usize) -> &[usize] {
+        &self.dependencies[entry]
+    }
+
+    /// Every entry, each after all of its dependencies. Independent
+    /// entries keep their file order.
+    pub fn mount_order(&self) -> Result<Vec<&'a FSTabEntry<'b>>, CycleError> {
+        let count = self.dependencies.len();
+        let mut mounted = vec![false; count];
+        let mut order = Vec::with_capacity(count);
+
+        while order.len() < count {
+            let next = (0..count).find(|&idx| {
+                !mounted[idx] && self.dependencies[idx].iter().all(|&dependency| mounted[dependency])
+            });
+            match next {
+                Some(idx) => {
+                    mounted[idx] = true;
+                    order.push(&self.fstab.entries[idx]);
+                }
+                None => return Err(self.find_cycle(&mounted)),
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Every entry, each before the entries it depends on, so nested
+    /// mounts are stopped before their parents.
+    pub fn unmount_order(&self) -> Result<Vec<&'a FSTabEntry<'b>>, CycleError> {
+        let mut order = self.mount_order()?;
+        order.reverse();
+        Ok(order)
+    }
+
+    /// Follow unmounted dependencies from the first unmountable entry
+    /// until one repeats. Every remaining entry waits on something, so
+    /// this always finds a loop.
+    fn find_cycle(&self, mounted: &[bool]) -> CycleError {
+        let mut path: Vec<usize> = vec![];
+        let mut current = (0..mounted.len()).find(|&idx| !mounted[idx]).expect("an unmounted entry");
+        loop {
+            if let Some(start) = path.iter().position(|&idx| idx == current) {
+                return CycleError {
+                    cycle: path[start..]
+                        .iter()
+                        .map(|&idx| name(&self.fstab.entries[idx]))
+                        .collect(),
+                };
+            }
+            path.push(current);
+            current = *self.dependencies[current]
+                .iter()
+                .find(|&&dependency| !mounted[dependency])
+                .expect("an unmounted dependency");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::parse_fstab;
+
+    fn files(entries: Vec<&FSTabEntry>) -> Vec<String> {
+        entries.iter().map(|entry| name(entry)).collect()
+    }
+
+    #[test]
+    fn nested_mounts() {
+        let fstab = parse_fstab("
+/dev/sda3 /home/foo ext4 defaults 0 2
+/dev/sda2 /home ext4 defaults 0 2
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda4 /homes ext4 defaults 0 2
+".lines());
+        let graph = MountGraph::new(&fstab);
+        assert_eq!(graph.dependencies(0), &[1]);
+        assert_eq!(graph.dependencies(1), &[2]);
+        assert_eq!(graph.dependencies(2), &[] as &[usize]);
+        assert_eq!(graph.dependencies(3), &[2]);
+        assert_eq!(
+            files(graph.mount_order().unwrap()),
+            vec!["/", "/home", "/home/foo", "/homes"]
+        );
+        assert_eq!(
+            files(graph.unmount_order().unwrap()),
+            vec!["/homes", "/home/foo", "/home", "/"]
+        );
+    }
+
+    #[test]
+    fn trailing_slash_mount_point() {
+        let fstab = parse_fstab("/dev/sda3 /home/foo/ ext4\n/dev/sda2 /home ext4\n/dev/foo /any/foo/ auto".lines());
+        let graph = MountGraph::new(&fstab);
+        assert_eq!(graph.dependencies(0), &[1]);
+        assert_eq!(graph.dependencies(2), &[] as &[usize]);
+        assert_eq!(files(graph.mount_order().unwrap()), vec!["/home", "/home/foo/", "/any/foo/"]);
+        assert_eq!(files(graph.unmount_order().unwrap()), vec!["/any/foo/", "/home/foo/", "/home"]);
+    }
+
+    #[test]
+    fn missing_parent_mount() {
+        let fstab = parse_fstab("/dev/sda3 /home/foo ext4\n/dev/sda5 /srv ext4".lines());
+        let graph = MountGraph::new(&fstab);
+        assert_eq!(graph.dependencies(0), &[] as &[usize]);
+        assert_eq!(files(graph.mount_order().unwrap()), vec!["/home/foo", "/srv"]);
+    }
+
+    #[test]
+    fn bind_mounts() {
+        let fstab = parse_fstab("
+/data/x /srv/x none bind
+/dev/sda1 / ext4 defaults 0 1
+/dev/sdb1 /data ext4 defaults 0 2
+/dev/sdb2 /srv ext4 defaults 0 2
+".lines());
+        let graph = MountGraph::new(&fstab);
+        assert_eq!(graph.dependencies(0), &[2, 3]);
+        assert_eq!(
+            files(graph.mount_order().unwrap()),
+            vec!["/", "/data", "/srv", "/srv/x"]
+        );
+    }
+
+    #[test]
+    fn overlay_layers() {
+        let fstab = parse_fstab("
+overlay /merged overlay lowerdir=/nix/store:/lower,upperdir=/persist/upper,workdir=/persist/work
+/dev/sda1 / ext4 defaults 0 1
+/dev/sdb1 /persist ext4 defaults 0 2
+/dev/sdc1 /nix/store ext4 defaults 0 2
+".lines());
+        let graph = MountGraph::new(&fstab);
+        assert_eq!(graph.dependencies(0), &[1, 2, 3]);
+        assert_eq!(
+            files(graph.mount_order().unwrap()),
+            vec!["/", "/persist", "/nix/store", "/merged"]
+        );
+    }
+
+    #[test]
+    fn requires_mounts_for() {
+        let fstab = parse_fstab("
+/dev/sda1 / ext4 defaults 0 1
+/var/lib/images/disk.img /mnt/img ext4 loop,x-systemd.requires-mounts-for=/var/lib/images
+/dev/sdb1 /var/lib ext4 defaults 0 2
+".lines());
+        let graph = MountGraph::new(&fstab);
+        assert_eq!(graph.dependencies(1), &[0, 2]);
+        assert_eq!(
+            files(graph.mount_order().unwrap()),
+            vec!["/", "/var/lib", "/mnt/img"]
+        );
+    }
+
+    #[test]
+    fn swap_files() {
+        let fstab = parse_fstab("
+/var/swapfile none swap
+/dev/sda2 none swap
+/dev/sda1 / ext4 defaults 0 1
+/dev/sdb1 /var ext4 defaults 0 2
+".lines());
+        let graph = MountGraph::new(&fstab);
+        assert_eq!(graph.dependencies(0), &[3]);
+        assert_eq!(graph.dependencies(1), &[] as &[usize]);
+        assert_eq!(
+            files(graph.mount_order().unwrap()),
+            vec!["/dev/sda2", "/", "/var", "/var/swapfile"]
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let fstab = parse_fstab("
+/dev/sda1 / ext4 defaults 0 1
+/mnt/b/src /mnt/a none bind
+/mnt/a/src /mnt/b none bind
+".lines());
+        let err = MountGraph::new(&fstab).mount_order().unwrap_err();
+        assert_eq!(
+            err,
+            CycleError {
+                cycle: vec!["/mnt/a".to_string(), "/mnt/b".to_string()],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "mount dependency cycle: /mnt/a -> /mnt/b -> /mnt/a"
+        );
+        assert!(MountGraph::new(&fstab).unmount_order().is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! Turning fstab changes into systemd unit actions.
+//!
+//! systemd-fstab-generator turns every fstab entry into a `.mount` or
+//! `.swap` unit, so applying a new fstab on a live system means
+//! stopping, starting, reloading or restarting those units.
+
+use fstab::diff::{diff, FstabDiff};
+use fstab::{FSTabEntry, FSTabFile};
+
+/// Mount points which hold the running system. Stopping them would
+/// pull the rug out from under it, so changes to them wait for a
+/// reboot instead.
+pub const PROTECTED_MOUNT_POINTS: &[&str] = &["/", "/nix", "/nix/store"];
+
+/// The units to act on to apply an fstab change
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MountActions {
+    /// Units for entries which were removed, or whose new entry is
+    /// `noauto`.
+    pub stop: Vec<String>,
+
+    /// Units for new entries which aren't `noauto`, and automount
+    /// units for new `x-systemd.automount` entries.
+    pub start: Vec<String>,
+
+    /// Mount units whose options changed. Reloading a mount unit
+    /// remounts it with the new options.
+    pub reload: Vec<String>,
+
+    /// Units whose device or type changed, which have to be stopped
+    /// and started again. Swap units whose options changed are here
+    /// too, since swap can't be changed in place.
+    pub restart: Vec<String>,
+
+    /// Units for protected mount points whose change can't be applied
+    /// without stopping them. A reboot is needed to apply these.
+    pub reboot_required: Vec<String>,
+}
+
+impl MountActions {
+    pub fn is_empty(&self) -> bool {
+        self.stop.is_empty()
+            && self.start.is_empty()
+            && self.reload.is_empty()
+            && self.restart.is_empty()
+            && self.reboot_required.is_empty()
+    }
+}
+
+fn is_protected(entry: &FSTabEntry) -> bool {
+    PROTECTED_MOUNT_POINTS
+        .iter()
+        .any(|protected| entry.file.trim_end_matches('/') == protected.trim_end_matches('/'))
+}
+
+fn unit(entry: &FSTabEntry) -> Option<String> {
+    entry.mount_unit().or_else(|| entry.swap_unit())
+}
+
+/// The automount unit systemd-fstab-generator creates for an
+/// `x-systemd.automount` entry. Unlike a mount unit, it is started even
+/// if the entry is `noauto`.
+fn automount_unit(entry: &FSTabEntry) -> Option<String> {
+    if !entry.systemd_options().map(|options| options.automount).unwrap_or(false) {
+        return None;
+    }
+    entry.mount_unit().map(|unit| format!("{}.automount", unit.trim_end_matches(".mount")))
+}
+
+/// Decide what to do with each unit to go from the old to new fstab.
+pub fn plan(old: &FSTabFile, new: &FSTabFile) -> MountActions {
+    MountActions::from_diff(&diff(old, new))
+}
+
+impl MountActions {
+    /// The actions for an already computed diff. Each unit appears in
+    /// at most one list.
+    pub fn from_diff(diff: &FstabDiff) -> MountActions {
+        let mut actions = MountActions::default();
+
+        for entry in &diff.removed {
+            if let Some(unit) = unit(entry) {
+                if is_protected(entry) {
+                    actions.reboot_required.push(unit);
+                } else {
+                    actions.stop.push(unit);
+                }
+            }
+        }
+
+        for entry in &diff.added {
+            if let Some(unit) = automount_unit(entry) {
+                actions.start.push(unit);
+            } else if let Some(unit) = unit(entry) {
+                if !entry.options.has("noauto") {
+                    actions.start.push(unit);
+                }
+            }
+        }
+
+        for change in &diff.options_changed {
+            if let Some(unit) = unit(change.new) {
+                if change.new.is_swap() {
+                    if change.new.options.has("noauto") {
+                        actions.stop.push(unit);
+                    } else {
+                        actions.restart.push(unit);
+                    }
+                } else {
+                    actions.reload.push(unit);
+                }
+            }
+        }
+
+        for change in &diff.device_changed {
+            if let Some(unit) = unit(change.new) {
+                if is_protected(change.new) {
+                    actions.reboot_required.push(unit);
+                } else if change.new.options.has("noauto") {
+                    actions.stop.push(unit);
+                } else {
+                    actions.restart.push(unit);
+                }
+            }
+        }
+
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::parse_fstab;
+
+    fn actions(old: &str, new: &str) -> MountActions {
+        plan(&parse_fstab(old.lines()), &parse_fstab(new.lines()))
+    }
+
+    fn units(units: &[&str]) -> Vec<String> {
+        units.iter().map(|unit| unit.to_string()).collect()
+    }
+
+    #[test]
+    fn no_changes() {
+        let fstab = "
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda2 none swap
+";
+        assert!(actions(fstab, fstab).is_empty());
+    }
+
+    #[test]
+    fn mounts() {
+        let result = actions(
+            "
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda2 /home ext4 defaults 0 2
+/dev/sda3 /srv ext4 defaults 0 2
+/dev/sda4 /var/lib ext4 defaults 0 2
+/dev/sda5 /data ext4 defaults 0 2
+",
+            "
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda2 /home ext4 noatime 0 2
+/dev/sdb3 /srv ext4 defaults 0 2
+/dev/sda6 /mnt/new-disk ext4 defaults 0 2
+/dev/sda7 /mnt/manual ext4 noauto 0 2
+",
+        );
+        assert_eq!(
+            result,
+            MountActions {
+                stop: units(&["var-lib.mount", "data.mount"]),
+                start: units(&["mnt-new\\x2ddisk.mount"]),
+                reload: units(&["home.mount"]),
+                restart: units(&["srv.mount"]),
+                reboot_required: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn device_changed_to_noauto_is_only_stopped() {
+        let result = actions(
+            "/dev/sda3 /srv ext4 defaults",
+            "/dev/sdb3 /srv ext4 noauto",
+        );
+        assert_eq!(result.stop, units(&["srv.mount"]));
+        assert!(result.restart.is_empty());
+    }
+
+    #[test]
+    fn automounts() {
+        let result = actions(
+            "/dev/sda1 / ext4 defaults 0 1",
+            "
+/dev/sda1 / ext4 defaults 0 1
+foo.com:/mnt/share /mnt/remote nfs noauto,x-systemd.automount,x-systemd.idle-timeout=600
+/dev/sdb1 /mnt/backup-disk ext4 x-systemd.automount 0 2
+/dev/sdc1 /mnt/broken ext4 x-systemd.automount,x-systemd.idle-timeout=soon 0 2
+",
+        );
+        assert_eq!(
+            result,
+            MountActions {
+                start: units(&["mnt-remote.automount", "mnt-backup\\x2ddisk.automount", "mnt-broken.mount"]),
+                ..MountActions::default()
+            }
+        );
+    }
+
+    #[test]
+    fn swaps() {
+        let result = actions(
+            "
+/dev/sda2 none swap
+/dev/sda3 none swap
+UUID=1f2aa318 swap swap defaults 0 0
+",
+            "
+/dev/sda2 none swap pri=10
+/dev/sda4 none swap
+UUID=1f2aa318 swap swap defaults 0 0
+",
+        );
+        assert_eq!(
+            result,
+            MountActions {
+                stop: units(&["dev-sda3.swap"]),
+                start: units(&["dev-sda4.swap"]),
+                reload: vec![],
+                restart: units(&["dev-sda2.swap"]),
+                reboot_required: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn protected_mounts_are_never_stopped() {
+        let result = actions(
+            "
+/dev/sda1 / ext4 defaults 0 1
+/dev/sda2 /nix ext4 defaults 0 2
+/dev/sda3 /nix/store ext4 defaults 0 2
+",
+            "
+/dev/sdb1 / ext4 noatime 0 1
+/dev/sda2 /nix/ ext4 noatime 0 2
+",
+        );
+        assert_eq!(
+            result,
+            MountActions {
+                stop: vec![],
+                start: vec![],
+                reload: units(&["nix.mount"]),
+                restart: vec![],
+                reboot_required: units(&["nix-store.mount", "-.mount"]),
+            }
+        );
+    }
+
+    #[test]
+    fn protected_mounts_can_be_remounted() {
+        let result = actions(
+            "/dev/sda1 / ext4 defaults 0 1",
+            "/dev/sda1 / ext4 noatime 0 1",
+        );
+        assert_eq!(result.reload, units(&["-.mount"]));
+    }
+}
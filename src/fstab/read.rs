@@ -0,0 +1,189 @@
+//! Reading an fstab from a file, pipe or anything else `BufRead`.
+//!
+//! The parsers in `fstab` borrow from the text they are given. These
+//! copy each entry as it is read, so the result outlives the reader.
+
+use fstab::{try_parse_fstab_line, FSTabEntry, FSTabFile, FstabError};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Every invalid line of an fstab which was read. This is the inner
+/// error of the `io::Error` returned by `FSTabFile::from_reader`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InvalidFstab {
+    /// The file the lines are from, if it was read from a path.
+    pub path: Option<PathBuf>,
+
+    pub errors: Vec<FstabError>,
+}
+
+impl fmt::Display for InvalidFstab {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid fstab")?;
+        if let Some(ref path) = self.path {
+            write!(f, " {}", path.display())?;
+        }
+        for error in &self.errors {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for InvalidFstab {}
+
+impl<'a> FSTabEntry<'a> {
+    /// Copy any borrowed strings so the entry no longer borrows the
+    /// text it was parsed from.
+    pub fn into_owned(self) -> FSTabEntry<'static> {
+        FSTabEntry {
+            spec: self.spec.into_owned().into(),
+            file: self.file.into_owned().into(),
+            fs_type: self.fs_type.into_owned().into(),
+            options: self.options.into_owned(),
+            dump: self.dump,
+            fsck_pass: self.fsck_pass,
+        }
+    }
+}
+
+impl<'a> FSTabFile<'a> {
+    pub fn into_owned(self) -> FSTabFile<'static> {
+        FSTabFile {
+            entries: self.entries.into_iter().map(FSTabEntry::into_owned).collect(),
+        }
+    }
+}
+
+/// Like `FSTabFile::from_reader`, but keep the 1-based line number of
+/// each entry. `path` is only used in the error.
+pub fn read_numbered_entries<R: BufRead>(reader: R, path: Option<&Path>) -> io::Result<Vec<(usize, FSTabEntry<'static>)>> {
+    let mut entries = vec![];
+    let mut errors = vec![];
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        match try_parse_fstab_line(&line) {
+            Ok(Some(entry)) => entries.push((idx + 1, entry.into_owned())),
+            Ok(None) => {}
+            Err(reason) => errors.push(FstabError {
+                line: idx + 1,
+                raw: line.clone(),
+                reason,
+            }),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            InvalidFstab {
+                path: path.map(Path::to_path_buf),
+                errors,
+            },
+        ))
+    }
+}
+
+/// Read the fstab at `path`, see `read_numbered_entries`.
+pub fn read_numbered_entries_from_path(path: &Path) -> io::Result<Vec<(usize, FSTabEntry<'static>)>> {
+    read_numbered_entries(BufReader::new(File::open(path)?), Some(path))
+}
+
+impl FSTabFile<'static> {
+    /// Read and parse an fstab, failing if any line is invalid, like
+    /// `parse_fstab_strict`.
+    ///
+    /// Invalid lines are an `io::ErrorKind::InvalidData` error whose
+    /// inner error is an `InvalidFstab` listing all of them.
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<FSTabFile<'static>> {
+        Ok(FSTabFile {
+            entries: read_numbered_entries(reader, None)?
+                .into_iter()
+                .map(|(_, entry)| entry)
+                .collect(),
+        })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<FSTabFile<'static>> {
+        Ok(FSTabFile {
+            entries: read_numbered_entries_from_path(path.as_ref())?
+                .into_iter()
+                .map(|(_, entry)| entry)
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::fixtures::{ESCAPES, MORBO, UTILLINUX_FSTAB, UTILLINUX_FSTAB_BROKEN, UTILLINUX_FSTAB_COMMENT};
+    use fstab::{parse_fstab, FstabErrorReason};
+    use test_util::TempDir;
+
+    #[test]
+    fn matches_parse_fstab() {
+        for text in &[MORBO, UTILLINUX_FSTAB, UTILLINUX_FSTAB_COMMENT, ESCAPES] {
+            assert_eq!(
+                FSTabFile::from_reader(text.as_bytes()).unwrap(),
+                parse_fstab(text.lines())
+            );
+        }
+    }
+
+    #[test]
+    fn outlives_the_text() {
+        let fstab = {
+            let text = String::from("LABEL=My\\040Disk /mnt/disk ext4 noatime 0 2\n");
+            FSTabFile::from_reader(text.as_bytes()).unwrap()
+        };
+        assert_eq!(fstab.entries[0].spec, "LABEL=My Disk");
+        assert_eq!(fstab.entries[0].options.to_string(), "noatime");
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let err = FSTabFile::from_reader(UTILLINUX_FSTAB_BROKEN.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let invalid = err.get_ref().unwrap().downcast_ref::<InvalidFstab>().unwrap();
+        let reasons = invalid.errors.iter()
+            .map(|error| (error.line, error.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![
+                (1, FstabErrorReason::TooFewFields),
+                (8, FstabErrorReason::TooManyFields),
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid fstab\n  line 1: too few fields: \"bug\"\n  line 8: too many fields: \"this is broken line with unexpected number of fields\""
+        );
+    }
+
+    #[test]
+    fn from_path() {
+        let dir = TempDir::new();
+        let path = dir.write("fstab", "/dev/sda1 / ext4 defaults 0 1\n");
+        let fstab = FSTabFile::from_path(&path).unwrap();
+        assert_eq!(fstab.entries.len(), 1);
+        assert_eq!(fstab.entries[0].file, "/");
+
+        let broken = dir.write("broken", "bug\n");
+        assert_eq!(
+            FSTabFile::from_path(&broken).unwrap_err().to_string(),
+            format!("invalid fstab {}\n  line 1: too few fields: \"bug\"", broken.display())
+        );
+
+        let err = FSTabFile::from_path(dir.path().join("missing")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,259 @@
+//! The first fstab field: what is being mounted.
+
+use fstab::FSTabEntry;
+
+/// What an fstab entry's `spec` refers to.
+///
+/// Tags are the `NAME=value` forms libmount and systemd resolve through
+/// the symlinks udev maintains under /dev/disk.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Source<'a> {
+    /// `UUID=3aa72460-7d05-4bd4-861f-6ef8b82082dc`
+    Uuid(&'a str),
+
+    /// `LABEL=nixos`
+    Label(&'a str),
+
+    /// `PARTUUID=5a7f2ad5-01`
+    PartUuid(&'a str),
+
+    /// `PARTLABEL=root`
+    PartLabel(&'a str),
+
+    /// `ID=ata-Samsung_SSD_850_EVO_250GB_S2R5NX0H123456-part1`
+    Id(&'a str),
+
+    /// An absolute path, usually a block device like `/dev/sda1` but
+    /// possibly a swap file or a bind mount's source directory.
+    Path(&'a str),
+
+    /// An NFS style `host:/export`. The host keeps any brackets
+    /// around an IPv6 address.
+    Remote { host: &'a str, path: &'a str },
+
+    /// A CIFS style `//server/share`.
+    Share { server: &'a str, share: &'a str },
+
+    /// Anything else, which names a pseudo filesystem like `tmpfs` or
+    /// `proc` rather than a device.
+    Virtual(&'a str),
+}
+
+/// Strip one pair of matching quotes, as libblkid does for tag values:
+/// `LABEL="my disk"`
+fn unquote(value: &str) -> &str {
+    for quote in &['"', '\''] {
+        if value.len() >= 2 && value.starts_with(*quote) && value.ends_with(*quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+/// Encode a tag value the way udev does when naming its /dev/disk
+/// symlinks, so `LABEL=my disk` becomes `by-label/my\x20disk`. See
+/// `encode_devnode_name` in systemd's src/shared/device-nodes.c.
+fn encode_devnode_name(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for chr in value.chars() {
+        if chr.is_ascii_alphanumeric() || "#+-.:=@_".contains(chr) || !chr.is_ascii() {
+            encoded.push(chr);
+        } else {
+            encoded.push_str(&format!("\\x{:02x}", chr as u32));
+        }
+    }
+    encoded
+}
+
+impl<'a> Source<'a> {
+    /// Classify an (already unescaped) spec field.
+    pub fn parse(spec: &'a str) -> Source<'a> {
+        if let Some(idx) = spec.find('=') {
+            let value = unquote(&spec[idx + 1..]);
+            match &spec[..idx] {
+                "UUID" => return Source::Uuid(value),
+                "LABEL" => return Source::Label(value),
+                "PARTUUID" => return Source::PartUuid(value),
+                "PARTLABEL" => return Source::PartLabel(value),
+                "ID" => return Source::Id(value),
+                _ => {}
+            }
+        }
+
+        if let Some(rest) = spec.strip_prefix("//") {
+            return match rest.find('/') {
+                Some(idx) => Source::Share {
+                    server: &rest[..idx],
+                    share: &rest[idx + 1..],
+                },
+                None => Source::Share {
+                    server: rest,
+                    share: "",
+                },
+            };
+        }
+
+        if spec.starts_with('/') {
+            return Source::Path(spec);
+        }
+
+        let host_end = if spec.starts_with('[') {
+            spec.find("]:").map(|idx| idx + 1)
+        } else {
+            spec.find(':')
+        };
+        if let Some(idx) = host_end {
+            return Source::Remote {
+                host: &spec[..idx],
+                path: &spec[idx + 1..],
+            };
+        }
+
+        Source::Virtual(spec)
+    }
+
+    /// Is this one of the `NAME=value` tags?
+    pub fn is_tag(&self) -> bool {
+        matches!(
+            *self,
+            Source::Uuid(_)
+                | Source::Label(_)
+                | Source::PartUuid(_)
+                | Source::PartLabel(_)
+                | Source::Id(_)
+        )
+    }
+
+    /// Is this on another machine?
+    pub fn is_network(&self) -> bool {
+        matches!(*self, Source::Remote { .. } | Source::Share { .. })
+    }
+
+    /// The block device node this source refers to: the udev symlink
+    /// for tags, or the path itself if it is under /dev. This is the
+    /// node systemd creates the mount's `.device` dependency for.
+    pub fn device_path(&self) -> Option<String> {
+        let (directory, value) = match *self {
+            Source::Uuid(value) => ("by-uuid", value),
+            Source::Label(value) => ("by-label", value),
+            Source::PartUuid(value) => ("by-partuuid", value),
+            Source::PartLabel(value) => ("by-partlabel", value),
+            Source::Id(value) => ("by-id", value),
+            Source::Path(path) if path.starts_with("/dev/") => return Some(path.to_string()),
+            _ => return None,
+        };
+        Some(format!("/dev/disk/{}/{}", directory, encode_devnode_name(value)))
+    }
+}
+
+impl<'a> FSTabEntry<'a> {
+    /// Parse this entry's `spec`.
+    pub fn source(&self) -> Source<'_> {
+        Source::parse(&self.spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tags() {
+        assert_eq!(
+            Source::parse("UUID=d3a8f783-df75-4dc8-9163-975a891052c0"),
+            Source::Uuid("d3a8f783-df75-4dc8-9163-975a891052c0")
+        );
+        assert_eq!(Source::parse("LABEL=nixos"), Source::Label("nixos"));
+        assert_eq!(Source::parse("PARTUUID=5a7f2ad5-01"), Source::PartUuid("5a7f2ad5-01"));
+        assert_eq!(Source::parse("PARTLABEL=root"), Source::PartLabel("root"));
+        assert_eq!(Source::parse("ID=ata-disk-part1"), Source::Id("ata-disk-part1"));
+    }
+
+    #[test]
+    fn parse_quoted_tags() {
+        assert_eq!(Source::parse("LABEL=\"my disk\""), Source::Label("my disk"));
+        assert_eq!(Source::parse("LABEL='my disk'"), Source::Label("my disk"));
+        assert_eq!(Source::parse("LABEL=\"unbalanced"), Source::Label("\"unbalanced"));
+    }
+
+    #[test]
+    fn parse_unknown_tag_is_virtual() {
+        assert_eq!(Source::parse("uuid=abc"), Source::Virtual("uuid=abc"));
+    }
+
+    #[test]
+    fn parse_paths() {
+        assert_eq!(Source::parse("/dev/mapper/foo"), Source::Path("/dev/mapper/foo"));
+        assert_eq!(Source::parse("/swapfile"), Source::Path("/swapfile"));
+    }
+
+    #[test]
+    fn parse_network() {
+        assert_eq!(
+            Source::parse("foo.com:/mnt/share"),
+            Source::Remote { host: "foo.com", path: "/mnt/share" }
+        );
+        assert_eq!(
+            Source::parse("user@host:/home/user"),
+            Source::Remote { host: "user@host", path: "/home/user" }
+        );
+        assert_eq!(
+            Source::parse("[fe80::1]:/export"),
+            Source::Remote { host: "[fe80::1]", path: "/export" }
+        );
+        assert_eq!(
+            Source::parse("//bar.com/gogogo"),
+            Source::Share { server: "bar.com", share: "gogogo" }
+        );
+        assert_eq!(
+            Source::parse("//bar.com/gogogo/deeper"),
+            Source::Share { server: "bar.com", share: "gogogo/deeper" }
+        );
+        assert!(Source::parse("//bar.com/gogogo").is_network());
+        assert!(!Source::parse("/dev/sda").is_network());
+    }
+
+    #[test]
+    fn parse_virtual() {
+        for name in &["tmpfs", "proc", "sysfs", "devpts", "none", "overlay"] {
+            assert_eq!(Source::parse(name), Source::Virtual(name));
+        }
+    }
+
+    #[test]
+    fn device_paths() {
+        assert_eq!(
+            Source::parse("UUID=d3a8f783-df75-4dc8-9163-975a891052c0").device_path(),
+            Some("/dev/disk/by-uuid/d3a8f783-df75-4dc8-9163-975a891052c0".to_string())
+        );
+        assert_eq!(
+            Source::parse("LABEL=\"my disk\"").device_path(),
+            Some("/dev/disk/by-label/my\\x20disk".to_string())
+        );
+        assert_eq!(
+            Source::parse("PARTLABEL=EFI/boot").device_path(),
+            Some("/dev/disk/by-partlabel/EFI\\x2fboot".to_string())
+        );
+        assert_eq!(
+            Source::parse("PARTUUID=5a7f2ad5-01").device_path(),
+            Some("/dev/disk/by-partuuid/5a7f2ad5-01".to_string())
+        );
+        assert_eq!(
+            Source::parse("ID=ata-disk-part1").device_path(),
+            Some("/dev/disk/by-id/ata-disk-part1".to_string())
+        );
+        assert_eq!(
+            Source::parse("/dev/mapper/foo").device_path(),
+            Some("/dev/mapper/foo".to_string())
+        );
+        assert_eq!(Source::parse("/swapfile").device_path(), None);
+        assert_eq!(Source::parse("tmpfs").device_path(), None);
+        assert_eq!(Source::parse("foo.com:/mnt/share").device_path(), None);
+    }
+
+    #[test]
+    fn entry_source() {
+        let entry = ::fstab::parse_fstab_line("LABEL=My\\040Disk /mnt ext4").unwrap();
+        assert_eq!(entry.source(), Source::Label("My Disk"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Mount options which are instructions to systemd rather than to
+//! mount(8).
+//!
+//! See `man 5 systemd.mount` under "FSTAB".
+
+use fstab::{FSTabEntry, MountOption, MountOptions};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+use timespan::parse_timespan;
+
+/// The options systemd-fstab-generator acts on
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SystemdMountOptions {
+    /// `noauto`: not pulled in by local-fs.target or remote-fs.target.
+    pub noauto: bool,
+
+    /// `nofail`: failing to mount doesn't fail the target.
+    pub nofail: bool,
+
+    /// `_netdev`: a network device, ordered after the network is up
+    /// even if the filesystem type isn't known to be networked.
+    pub netdev: bool,
+
+    /// `x-initrd.mount`: mounted in the initrd.
+    pub initrd: bool,
+
+    /// `x-systemd.automount`: mounted on first access by an automount
+    /// unit.
+    pub automount: bool,
+
+    /// `x-systemd.makefs`: a filesystem is created if there isn't one.
+    pub makefs: bool,
+
+    /// `x-systemd.growfs`: the filesystem is grown to its device.
+    pub growfs: bool,
+
+    /// `x-systemd.requires=`: units which are required and ordered
+    /// before the mount.
+    pub requires: Vec<String>,
+
+    /// `x-systemd.before=`
+    pub before: Vec<String>,
+
+    /// `x-systemd.after=`
+    pub after: Vec<String>,
+
+    /// `x-systemd.wanted-by=`
+    pub wanted_by: Vec<String>,
+
+    /// `x-systemd.required-by=`
+    pub required_by: Vec<String>,
+
+    /// `x-systemd.requires-mounts-for=`: paths whose mounts are
+    /// required and ordered before this one.
+    pub requires_mounts_for: Vec<String>,
+
+    /// `x-systemd.device-timeout=`: how long to wait for the device.
+    pub device_timeout: Option<Duration>,
+
+    /// `x-systemd.mount-timeout=`: how long mount(8) may take.
+    pub mount_timeout: Option<Duration>,
+
+    /// `x-systemd.idle-timeout=`: how long an automount may stay idle
+    /// before it is unmounted.
+    pub idle_timeout: Option<Duration>,
+}
+
+/// An option with a value systemd can't use
+#[derive(Debug, PartialEq, Clone)]
+pub struct InvalidSystemdOption {
+    /// The option exactly as written.
+    pub option: String,
+}
+
+impl fmt::Display for InvalidSystemdOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid systemd mount option: {:?}", self.option)
+    }
+}
+
+impl Error for InvalidSystemdOption {}
+
+/// Is this option consumed by systemd instead of being passed to
+/// mount(8)?
+pub fn is_systemd_option(option: &MountOption) -> bool {
+    option.name.starts_with("x-systemd.")
+        || option.name == "x-initrd.mount"
+        || option.name == "noauto"
+        || option.name == "auto"
+        || option.name == "nofail"
+        || option.name == "_netdev"
+}
+
+impl SystemdMountOptions {
+    /// Pull the systemd options out of a list of mount options. Options
+    /// systemd doesn't know about are ignored, but a known option with
+    /// a value it can't use is an error.
+    ///
+    /// As with systemd, a later `noauto` or `auto` overrides an earlier
+    /// one, and unit lists accumulate. Lists are space separated.
+    pub fn from_options(options: &MountOptions) -> Result<SystemdMountOptions, InvalidSystemdOption> {
+        let mut parsed = SystemdMountOptions::default();
+
+        for option in options {
+            let invalid = || InvalidSystemdOption {
+                option: option.to_string(),
+            };
+            let value = option.unquoted_value();
+            let list = |list: &mut Vec<String>| -> Result<(), InvalidSystemdOption> {
+                let value = value.ok_or_else(invalid)?;
+                list.extend(value.split_whitespace().map(String::from));
+                Ok(())
+            };
+            let duration = || value.and_then(parse_timespan).map(Some).ok_or_else(invalid);
+
+            match &option.name[..] {
+                "noauto" => parsed.noauto = true,
+                "auto" => parsed.noauto = false,
+                "nofail" => parsed.nofail = true,
+                "_netdev" => parsed.netdev = true,
+                "x-initrd.mount" => parsed.initrd = true,
+                "x-systemd.automount" => parsed.automount = true,
+                "x-systemd.makefs" => parsed.makefs = true,
+                "x-systemd.growfs" => parsed.growfs = true,
+                "x-systemd.requires" => list(&mut parsed.requires)?,
+                "x-systemd.before" => list(&mut parsed.before)?,
+                "x-systemd.after" => list(&mut parsed.after)?,
+                "x-systemd.wanted-by" => list(&mut parsed.wanted_by)?,
+                "x-systemd.required-by" => list(&mut parsed.required_by)?,
+                "x-systemd.requires-mounts-for" => list(&mut parsed.requires_mounts_for)?,
+                "x-systemd.device-timeout" => parsed.device_timeout = duration()?,
+                "x-systemd.mount-timeout" => parsed.mount_timeout = duration()?,
+                "x-systemd.idle-timeout" => parsed.idle_timeout = duration()?,
+                _ => {}
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl<'a> MountOptions<'a> {
+    /// The options left for mount(8) once the ones systemd consumes
+    /// are removed, see `is_systemd_option`.
+    pub fn without_systemd_options(&self) -> MountOptions<'a> {
+        self.iter()
+            .filter(|option| !is_systemd_option(option))
+            .cloned()
+            .collect()
+    }
+}
+
+impl<'a> FSTabEntry<'a> {
+    /// Parse the systemd specific options of this entry.
+    pub fn systemd_options(&self) -> Result<SystemdMountOptions, InvalidSystemdOption> {
+        SystemdMountOptions::from_options(&self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(options: &str) -> Result<SystemdMountOptions, InvalidSystemdOption> {
+        SystemdMountOptions::from_options(&MountOptions::parse(options))
+    }
+
+    #[test]
+    fn plain_options() {
+        assert_eq!(parse("defaults,noatime"), Ok(SystemdMountOptions::default()));
+    }
+
+    #[test]
+    fn flags() {
+        let parsed = parse("noauto,nofail,_netdev,x-initrd.mount,x-systemd.automount,x-systemd.makefs,x-systemd.growfs").unwrap();
+        assert!(parsed.noauto);
+        assert!(parsed.nofail);
+        assert!(parsed.netdev);
+        assert!(parsed.initrd);
+        assert!(parsed.automount);
+        assert!(parsed.makefs);
+        assert!(parsed.growfs);
+    }
+
+    #[test]
+    fn later_auto_wins() {
+        assert!(!parse("noauto,auto").unwrap().noauto);
+        assert!(parse("auto,noauto").unwrap().noauto);
+    }
+
+    #[test]
+    fn unit_lists() {
+        let parsed = parse(
+            "x-systemd.requires=network-online.target,x-systemd.requires=foo.service bar.service,x-systemd.after=a.service,x-systemd.before=b.service,x-systemd.wanted-by=c.target,x-systemd.required-by=d.target,x-systemd.requires-mounts-for=/nix/store"
+        ).unwrap();
+        assert_eq!(parsed.requires, vec!["network-online.target", "foo.service", "bar.service"]);
+        assert_eq!(parsed.after, vec!["a.service"]);
+        assert_eq!(parsed.before, vec!["b.service"]);
+        assert_eq!(parsed.wanted_by, vec!["c.target"]);
+        assert_eq!(parsed.required_by, vec!["d.target"]);
+        assert_eq!(parsed.requires_mounts_for, vec!["/nix/store"]);
+    }
+
+    #[test]
+    fn durations() {
+        let parsed = parse(
+            "x-systemd.device-timeout=1min30s,x-systemd.mount-timeout=90,x-systemd.idle-timeout=infinity"
+        ).unwrap();
+        assert_eq!(parsed.device_timeout, Some(Duration::from_secs(90)));
+        assert_eq!(parsed.mount_timeout, Some(Duration::from_secs(90)));
+        assert_eq!(parsed.idle_timeout, Some(Duration::MAX));
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(
+            parse("x-systemd.device-timeout=soon"),
+            Err(InvalidSystemdOption { option: "x-systemd.device-timeout=soon".to_string() })
+        );
+        assert_eq!(
+            parse("x-systemd.requires"),
+            Err(InvalidSystemdOption { option: "x-systemd.requires".to_string() })
+        );
+    }
+
+    #[test]
+    fn unknown_systemd_options_are_ignored() {
+        assert_eq!(parse("x-systemd.rw-only,x-foo=bar"), Ok(SystemdMountOptions::default()));
+    }
+
+    #[test]
+    fn without_systemd_options() {
+        let options = MountOptions::parse(
+            "noatime,x-systemd.automount,nofail,x-systemd.idle-timeout=5min,_netdev,x-initrd.mount,user=SRGROUP/baby,noauto,auto,x-gvfs-show"
+        );
+        assert_eq!(
+            options.without_systemd_options().to_string(),
+            "noatime,user=SRGROUP/baby,x-gvfs-show"
+        );
+    }
+
+    #[test]
+    fn entry_systemd_options() {
+        let entry = ::fstab::parse_fstab_line(
+            "foo.com:/mnt/share /mnt/remote nfs noauto,x-systemd.automount,x-systemd.idle-timeout=600"
+        ).unwrap();
+        let parsed = entry.systemd_options().unwrap();
+        assert!(parsed.noauto);
+        assert!(parsed.automount);
+        assert_eq!(parsed.idle_timeout, Some(Duration::from_secs(600)));
+    }
+}
@@ -0,0 +1,253 @@
+//! Writing fstab entries back out as text.
+//!
+//! `Display` on `FSTabEntry` and `FSTabFile` writes the most compact
+//! form which parses back to the same values. `FSTabFile::display`
+//! takes `WriteOptions` for anything else.
+
+use fstab::{FSTabEntry, FSTabFile};
+use mangle::mangle;
+use std::borrow::Cow;
+use std::fmt;
+
+/// How to lay out written entries
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct WriteOptions {
+    /// Always write the dump and fsck pass fields. Otherwise they are
+    /// left off when they are 0, since that is their default. Empty
+    /// options are written as `defaults` to make room for them.
+    pub always_dump_pass: bool,
+
+    /// Pad fields with spaces so every entry's columns line up.
+    pub align_columns: bool,
+}
+
+impl<'a> FSTabEntry<'a> {
+    /// The fields of this entry as they should be written, with spec
+    /// and file escaped. Trailing fields which are at their defaults
+    /// are left off, unless `always_dump_pass` is set.
+    ///
+    /// Empty options are written as `defaults` when a later field
+    /// follows them, because an empty field can't be written.
+    pub fn fields(&self, write_options: WriteOptions) -> Vec<Cow<'_, str>> {
+        let mut fields = vec![
+            mangle(&self.spec),
+            mangle(&self.file),
+            Cow::Borrowed(&self.fs_type),
+        ];
+
+        let count = if write_options.always_dump_pass || self.fsck_pass != 0 {
+            6
+        } else if self.dump != 0 {
+            5
+        } else if !self.options.is_empty() {
+            4
+        } else {
+            3
+        };
+
+        if count >= 4 {
+            fields.push(if self.options.is_empty() {
+                Cow::Borrowed("defaults")
+            } else {
+                Cow::Owned(self.options.to_string())
+            });
+        }
+        if count >= 5 {
+            fields.push(Cow::Owned(self.dump.to_string()));
+        }
+        if count >= 6 {
+            fields.push(Cow::Owned(self.fsck_pass.to_string()));
+        }
+
+        fields
+    }
+}
+
+impl<'a> fmt::Display for FSTabEntry<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.fields(WriteOptions::default()).join(" "))
+    }
+}
+
+/// An `FSTabFile` being written with specific `WriteOptions`, see
+/// `FSTabFile::display`.
+pub struct DisplayFstab<'a, 'b: 'a> {
+    fstab: &'a FSTabFile<'b>,
+    write_options: WriteOptions,
+}
+
+impl<'a> FSTabFile<'a> {
+    /// Write this file with non-default `WriteOptions`, ie:
+    /// `fstab.display(options).to_string()`
+    pub fn display<'b>(&'b self, write_options: WriteOptions) -> DisplayFstab<'b, 'a> {
+        DisplayFstab {
+            fstab: self,
+            write_options,
+        }
+    }
+}
+
+impl<'a, 'b> fmt::Display for DisplayFstab<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = self
+            .fstab
+            .entries
+            .iter()
+            .map(|entry| entry.fields(self.write_options))
+            .collect::<Vec<Vec<Cow<str>>>>();
+
+        let mut widths = vec![];
+        if self.write_options.align_columns {
+            for row in &rows {
+                for (idx, field) in row.iter().enumerate() {
+                    let width = field.chars().count();
+                    if idx >= widths.len() {
+                        widths.push(width);
+                    } else if width > widths[idx] {
+                        widths[idx] = width;
+                    }
+                }
+            }
+        }
+
+        for row in &rows {
+            for (idx, field) in row.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, " ")?;
+                }
+                if idx + 1 < row.len() && idx < widths.len() {
+                    write!(f, "{:width$}", field, width = widths[idx])?;
+                } else {
+                    write!(f, "{}", field)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for FSTabFile<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.display(WriteOptions::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::fixtures::ALL;
+    use fstab::parse_fstab;
+
+    #[test]
+    fn entry_compact() {
+        for line in &[
+            "/dev/sda1 / ext4 defaults 0 1",
+            "/dev/sda1 / ext4 defaults 1",
+            "/dev/sda1 / ext4 defaults",
+            "/dev/sda1 / ext4",
+            "/dev/sda1 /mnt/My\\040Disk\\011Tab\\134 ext4",
+        ] {
+            let entry = ::fstab::parse_fstab_line(line).unwrap();
+            assert_eq!(&entry.to_string(), line);
+        }
+    }
+
+    #[test]
+    fn entry_normalizes_whitespace_and_zeros() {
+        let entry = ::fstab::parse_fstab_line("  tmpfs\t/dev/shm  tmpfs defaults 0 0").unwrap();
+        assert_eq!(entry.to_string(), "tmpfs /dev/shm tmpfs defaults");
+    }
+
+    #[test]
+    fn entry_empty_options_before_dump() {
+        let mut entry = ::fstab::parse_fstab_line("/dev/sda1 / ext4").unwrap();
+        entry.fsck_pass = 1;
+        assert_eq!(entry.to_string(), "/dev/sda1 / ext4 defaults 0 1");
+    }
+
+    #[test]
+    fn file_always_dump_pass() {
+        let fstab = parse_fstab("/dev/sda1 / ext4\n/dev/sda2 /home ext4 noatime 0 2".lines());
+        assert_eq!(
+            fstab
+                .display(WriteOptions {
+                    always_dump_pass: true,
+                    ..Default::default()
+                })
+                .to_string(),
+            "/dev/sda1 / ext4 defaults 0 0\n/dev/sda2 /home ext4 noatime 0 2\n"
+        );
+    }
+
+    #[test]
+    fn file_aligned() {
+        let fstab = parse_fstab(
+            "UUID=d3a8f783-df75-4dc8-9163-975a891052c0 / ext3 noatime,defaults 1 1
+tmpfs /dev/shm tmpfs defaults 0 0
+foo.com:/mnt/share /mnt/remote nfs noauto"
+                .lines(),
+        );
+        assert_eq!(
+            fstab
+                .display(WriteOptions {
+                    always_dump_pass: true,
+                    align_columns: true,
+                })
+                .to_string(),
+            "UUID=d3a8f783-df75-4dc8-9163-975a891052c0 /           ext3  noatime,defaults 1 1
+tmpfs                                     /dev/shm    tmpfs defaults         0 0
+foo.com:/mnt/share                        /mnt/remote nfs   noauto           0 0
+"
+        );
+    }
+
+    #[test]
+    fn file_empty() {
+        assert_eq!(parse_fstab("".lines()).to_string(), "");
+    }
+
+    #[test]
+    fn parse_serialize_round_trip() {
+        for text in ALL {
+            let fstab = parse_fstab(text.lines());
+            assert!(!fstab.entries.is_empty());
+            for align_columns in &[false, true] {
+                let written = fstab
+                    .display(WriteOptions {
+                        always_dump_pass: false,
+                        align_columns: *align_columns,
+                    })
+                    .to_string();
+                assert_eq!(parse_fstab(written.lines()), fstab, "{}", written);
+            }
+            for entry in &fstab.entries {
+                let written = entry.to_string();
+                assert_eq!(::fstab::parse_fstab_line(&written).as_ref(), Some(entry));
+            }
+        }
+    }
+
+    #[test]
+    fn parse_serialize_round_trip_always_dump_pass() {
+        // Writing the dump and pass fields forces empty options to be
+        // written as "defaults", so compare against that.
+        for text in ALL {
+            let mut fstab = parse_fstab(text.lines());
+            for align_columns in &[false, true] {
+                let written = fstab
+                    .display(WriteOptions {
+                        always_dump_pass: true,
+                        align_columns: *align_columns,
+                    })
+                    .to_string();
+                for entry in fstab.entries.iter_mut() {
+                    if entry.options.is_empty() {
+                        entry.options = "defaults".into();
+                    }
+                }
+                assert_eq!(parse_fstab(written.lines()), fstab, "{}", written);
+            }
+        }
+    }
+}
@@ -0,0 +1,16 @@
+#[macro_use]
+extern crate lazy_static;
+extern crate regex;
+
+pub mod cli;
+pub mod crypttab;
+pub mod device;
+pub mod fstab;
+mod mangle;
+pub mod mountinfo;
+pub mod swaps;
+#[cfg(test)]
+mod test_util;
+pub mod timespan;
+pub mod unit_file;
+pub mod unit_name;
@@ -1,9 +1,135 @@
+extern crate activate;
 
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
-mod fstab;
+use activate::cli::{self, Action, Args, Outcome};
+use activate::fstab::{plan, FSTabFile};
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+/// The fstab of the running system.
+const CURRENT_FSTAB: &str = "/etc/fstab";
 
 fn main() {
-    println!("Hello, world!");
+    let args = match cli::parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprint!("error: {}\n\n{}", err, cli::USAGE);
+            process::exit(Outcome::Usage.code());
+        }
+    };
+
+    let outcome = run(&args).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        Outcome::Error
+    });
+    process::exit(outcome.code());
+}
+
+/// `--toplevel`, or the toplevel this executable is installed in.
+fn find_toplevel(args: &Args) -> Result<PathBuf, String> {
+    if let Some(ref toplevel) = args.toplevel {
+        return Ok(toplevel.clone());
+    }
+
+    let exe = env::current_exe()
+        .and_then(|exe| exe.canonicalize())
+        .map_err(|err| format!("can't find the running executable: {}", err))?;
+    cli::toplevel_from_exe(&exe)
+        .map(Path::to_path_buf)
+        .ok_or_else(|| format!("{} isn't in a toplevel's bin directory, pass --toplevel", exe.display()))
+}
+
+/// Read an fstab, refusing to go on if any line is invalid: a dropped
+/// line would look like a filesystem to unmount.
+fn read_fstab(path: &Path) -> Result<FSTabFile<'static>, String> {
+    match FSTabFile::from_path(path) {
+        Ok(fstab) => Ok(fstab),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound && path == Path::new(CURRENT_FSTAB) => {
+            Ok(FSTabFile { entries: vec![] })
+        }
+        Err(err) => Err(format!("refusing to activate, can't read {}: {}", path.display(), err)),
+    }
+}
+
+/// Run `systemctl <verb>` on some units, reporting them first. Returns
+/// whether it succeeded.
+fn systemctl(verb: &str, doing: &str, units: &[String]) -> bool {
+    if units.is_empty() {
+        return true;
+    }
+    eprintln!("{} the following units: {}", doing, units.join(", "));
+    Command::new("systemctl")
+        .arg(verb)
+        .arg("--")
+        .args(units)
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn run(args: &Args) -> Result<Outcome, String> {
+    let toplevel = find_toplevel(args)?;
+    if !Path::new("/etc/NIXOS").exists() {
+        return Err("this is not a NixOS installation".to_string());
+    }
+
+    let new = read_fstab(&toplevel.join("etc/fstab"))?;
+    let old = read_fstab(Path::new(CURRENT_FSTAB))?;
+    let actions = plan(&old, &new);
+
+    if args.action == Action::DryActivate {
+        print!("{}", cli::describe_dry_run(&actions));
+        return Ok(Outcome::Success);
+    }
+
+    if args.action.installs_boot_loader() {
+        let installer = env::var_os("INSTALL_BOOTLOADER")
+            .ok_or_else(|| "can't install the boot loader, INSTALL_BOOTLOADER is not set".to_string())?;
+        let installed = Command::new(&installer)
+            .arg(&toplevel)
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        if !installed {
+            return Err("failed to install the boot loader".to_string());
+        }
+    }
+
+    if !args.action.activates() {
+        return Ok(Outcome::Success);
+    }
+
+    let mut ok = systemctl("stop", "stopping", &actions.stop);
+
+    eprintln!("activating the configuration...");
+    ok &= Command::new(toplevel.join("activate"))
+        .arg(&toplevel)
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+
+    // Let systemd-fstab-generator write the new .mount and .swap units
+    // before they are acted on.
+    ok &= Command::new("systemctl")
+        .arg("daemon-reload")
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+
+    ok &= systemctl("reload", "reloading", &actions.reload);
+    ok &= systemctl("restart", "restarting", &actions.restart);
+    ok &= systemctl("start", "starting", &actions.start);
+
+    if !ok {
+        Ok(Outcome::UnitsFailed)
+    } else if !actions.reboot_required.is_empty() {
+        eprintln!(
+            "a reboot is needed to apply the following units: {}",
+            actions.reboot_required.join(", ")
+        );
+        Ok(Outcome::RebootRecommended)
+    } else {
+        Ok(Outcome::Success)
+    }
 }
@@ -0,0 +1,128 @@
+//! Encoding and decoding of the octal escapes used in fstab(5), mtab
+//! and /proc/self/mountinfo fields.
+//!
+//! The kernel and util-linux encode space, tab, newline and backslash
+//! as `\040`, `\011`, `\012` and `\134` so they don't collide with the
+//! whitespace field separators. See util-linux's lib/mangle.c.
+
+use std::borrow::Cow;
+
+fn is_octal(byte: u8) -> bool {
+    (b'0'..=b'7').contains(&byte)
+}
+
+/// Decode `\ooo` octal escapes, as util-linux's `unmangle` does.
+///
+/// A backslash which is not followed by exactly three octal digits is
+/// kept as-is. If there is nothing to decode the input is borrowed.
+///
+/// Returns None if the decoded bytes are not valid UTF-8, for example
+/// `\377`.
+pub fn unmangle<'a>(field: &'a str) -> Option<Cow<'a, str>> {
+    if !field.contains('\\') {
+        return Some(Cow::Borrowed(field));
+    }
+
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && i + 3 < bytes.len()
+            && is_octal(bytes[i + 1])
+            && is_octal(bytes[i + 2])
+            && is_octal(bytes[i + 3])
+        {
+            let value = 64 * u32::from(bytes[i + 1] - b'0')
+                + 8 * u32::from(bytes[i + 2] - b'0')
+                + u32::from(bytes[i + 3] - b'0');
+            decoded.push(value as u8);
+            i += 4;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok().map(Cow::Owned)
+}
+
+/// Encode space, tab, newline and backslash as octal escapes, as
+/// util-linux's `mangle` does, so the result can be written as a
+/// single whitespace-separated field.
+///
+/// If there is nothing to encode the input is borrowed.
+pub fn mangle<'a>(field: &'a str) -> Cow<'a, str> {
+    if !field.contains([' ', '\t', '\n', '\\']) {
+        return Cow::Borrowed(field);
+    }
+
+    let mut encoded = String::with_capacity(field.len() + 6);
+    for chr in field.chars() {
+        match chr {
+            ' ' => encoded.push_str("\\040"),
+            '\t' => encoded.push_str("\\011"),
+            '\n' => encoded.push_str("\\012"),
+            '\\' => encoded.push_str("\\134"),
+            _ => encoded.push(chr),
+        }
+    }
+    Cow::Owned(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unmangle_borrows_when_unescaped() {
+        match unmangle("/mnt/disk") {
+            Some(Cow::Borrowed("/mnt/disk")) => {}
+            other => panic!("expected a borrowed value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unmangle_utillinux_escapes() {
+        // These mirror util-linux's lib/mangle.c test program, which
+        // unmangles "\040foo\011bar" and friends.
+        assert_eq!(unmangle("\\040foo\\011bar").unwrap(), " foo\tbar");
+        assert_eq!(unmangle("/mnt/My\\040Disk").unwrap(), "/mnt/My Disk");
+        assert_eq!(unmangle("new\\012line").unwrap(), "new\nline");
+        assert_eq!(unmangle("back\\134slash").unwrap(), "back\\slash");
+        assert_eq!(unmangle("\\040\\040").unwrap(), "  ");
+    }
+
+    #[test]
+    fn unmangle_incomplete_escapes_are_kept() {
+        assert_eq!(unmangle("foo\\").unwrap(), "foo\\");
+        assert_eq!(unmangle("foo\\04").unwrap(), "foo\\04");
+        assert_eq!(unmangle("foo\\048").unwrap(), "foo\\048");
+        assert_eq!(unmangle("foo\\x20").unwrap(), "foo\\x20");
+        assert_eq!(unmangle("\\0400").unwrap(), " 0");
+    }
+
+    #[test]
+    fn unmangle_invalid_utf8() {
+        assert_eq!(unmangle("\\377"), None);
+    }
+
+    #[test]
+    fn mangle_borrows_when_clean() {
+        assert!(matches!(mangle("/mnt/disk"), Cow::Borrowed("/mnt/disk")));
+    }
+
+    #[test]
+    fn mangle_escapes() {
+        assert_eq!(mangle(" foo\tbar"), "\\040foo\\011bar");
+        assert_eq!(mangle("new\nline"), "new\\012line");
+        assert_eq!(mangle("back\\slash"), "back\\134slash");
+    }
+
+    #[test]
+    fn mangle_unmangle_round_trip() {
+        for input in &["/mnt/My Disk", "a\tb\nc\\d", "\\040", "plain"] {
+            assert_eq!(unmangle(&mangle(input)).unwrap(), *input);
+        }
+    }
+}
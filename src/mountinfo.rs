@@ -0,0 +1,377 @@
+//! The live mount table, as reported by /proc/self/mountinfo.
+//!
+//! See `man 5 proc` and the kernel's
+//! Documentation/filesystems/proc.rst. Each line looks like:
+//!
+//! ```text
+//! 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+//! ```
+
+use fstab::MountOptions;
+use mangle::unmangle;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// One of the optional fields describing mount propagation
+#[derive(Debug, PartialEq, Clone)]
+pub enum Propagation {
+    /// `shared:X`, the mount is in peer group X.
+    Shared(u32),
+
+    /// `master:X`, the mount is a slave to peer group X.
+    Master(u32),
+
+    /// `propagate_from:X`, the mount receives propagation from peer
+    /// group X, which is the closest dominant peer group.
+    PropagateFrom(u32),
+
+    /// `unbindable`
+    Unbindable,
+
+    /// A field this parser doesn't know about. The kernel documents
+    /// that new optional fields may be added.
+    Other(String),
+}
+
+impl Propagation {
+    pub fn parse(field: &str) -> Propagation {
+        if field == "unbindable" {
+            return Propagation::Unbindable;
+        }
+
+        if let Some(idx) = field.find(':') {
+            if let Ok(group) = field[idx + 1..].parse::<u32>() {
+                match &field[..idx] {
+                    "shared" => return Propagation::Shared(group),
+                    "master" => return Propagation::Master(group),
+                    "propagate_from" => return Propagation::PropagateFrom(group),
+                    _ => {}
+                }
+            }
+        }
+
+        Propagation::Other(field.to_string())
+    }
+}
+
+/// A single mounted filesystem
+#[derive(Debug, PartialEq, Clone)]
+pub struct MountInfo {
+    /// A unique ID for the mount, which may be reused after umount.
+    pub mount_id: u32,
+
+    /// The ID of the parent mount, or of itself for the root of the
+    /// mount namespace.
+    pub parent_id: u32,
+
+    /// The major device number of the filesystem.
+    pub major: u32,
+
+    /// The minor device number of the filesystem.
+    pub minor: u32,
+
+    /// The directory within the filesystem which forms the root of
+    /// this mount. Anything other than `/` is a bind mount of a
+    /// subdirectory.
+    pub root: String,
+
+    /// Where the filesystem is mounted, relative to the process's
+    /// root.
+    pub mount_point: String,
+
+    /// Options which apply to this mount point only, ie: `rw,noatime`.
+    pub mount_options: MountOptions<'static>,
+
+    /// Zero or more optional fields, in the order the kernel wrote
+    /// them.
+    pub propagation: Vec<Propagation>,
+
+    /// The filesystem type, ie: `ext4` or `fuse.sshfs`.
+    pub fs_type: String,
+
+    /// The filesystem specific source, ie: `/dev/sda1`, or `none`.
+    pub source: String,
+
+    /// Options which apply to the whole superblock.
+    pub super_options: MountOptions<'static>,
+}
+
+/// Why a line of mountinfo could not be parsed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MountInfoErrorReason {
+    /// The line ended before all of the fields were read.
+    TooFewFields,
+
+    /// The mount ID or parent ID isn't a number.
+    InvalidId,
+
+    /// The device isn't `major:minor`.
+    InvalidDevice,
+
+    /// The `-` separating the optional fields was missing.
+    MissingSeparator,
+
+    /// An octal escape decoded to something which isn't valid UTF-8.
+    BadEscape,
+}
+
+impl fmt::Display for MountInfoErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            MountInfoErrorReason::TooFewFields => "too few fields",
+            MountInfoErrorReason::InvalidId => "mount ID is not a number",
+            MountInfoErrorReason::InvalidDevice => "device is not major:minor",
+            MountInfoErrorReason::MissingSeparator => "missing optional field separator",
+            MountInfoErrorReason::BadEscape => "octal escape is not valid UTF-8",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// A line which could not be parsed, and why
+#[derive(Debug, PartialEq, Clone)]
+pub struct MountInfoError {
+    /// The 1-based line number.
+    pub line: usize,
+
+    /// The line exactly as it was read.
+    pub raw: String,
+
+    pub reason: MountInfoErrorReason,
+}
+
+impl fmt::Display for MountInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mountinfo line {}: {}: {:?}", self.line, self.reason, self.raw)
+    }
+}
+
+impl Error for MountInfoError {}
+
+fn unescape(field: &str) -> Result<String, MountInfoErrorReason> {
+    unmangle(field)
+        .map(|field| field.into_owned())
+        .ok_or(MountInfoErrorReason::BadEscape)
+}
+
+fn id(field: &str) -> Result<u32, MountInfoErrorReason> {
+    field.parse::<u32>().map_err(|_| MountInfoErrorReason::InvalidId)
+}
+
+/// Parse a single line of mountinfo.
+pub fn parse_mountinfo_line(line: &str) -> Result<MountInfo, MountInfoErrorReason> {
+    let mut fields = line.split_whitespace();
+    let mut next = || fields.next().ok_or(MountInfoErrorReason::TooFewFields);
+
+    let mount_id = id(next()?)?;
+    let parent_id = id(next()?)?;
+
+    let device = next()?;
+    let idx = device.find(':').ok_or(MountInfoErrorReason::InvalidDevice)?;
+    let major = device[..idx].parse::<u32>().map_err(|_| MountInfoErrorReason::InvalidDevice)?;
+    let minor = device[idx + 1..].parse::<u32>().map_err(|_| MountInfoErrorReason::InvalidDevice)?;
+
+    let root = unescape(next()?)?;
+    let mount_point = unescape(next()?)?;
+    let mount_options = MountOptions::parse(next()?).into_owned();
+
+    let mut propagation = vec![];
+    loop {
+        match next() {
+            Ok("-") => break,
+            Ok(field) => propagation.push(Propagation::parse(field)),
+            Err(_) => return Err(MountInfoErrorReason::MissingSeparator),
+        }
+    }
+
+    let fs_type = unescape(next()?)?;
+    let source = unescape(next()?)?;
+    // Some filesystems have no super options at all.
+    let super_options = MountOptions::parse(next().unwrap_or("")).into_owned();
+
+    Ok(MountInfo {
+        mount_id,
+        parent_id,
+        major,
+        minor,
+        root,
+        mount_point,
+        mount_options,
+        propagation,
+        fs_type,
+        source,
+        super_options,
+    })
+}
+
+/// Every mount in a mount namespace, in the order the kernel listed
+/// them: parents before their children, and later mounts over the
+/// same point after earlier ones.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MountTable {
+    pub mounts: Vec<MountInfo>,
+}
+
+impl MountTable {
+    /// Parse mountinfo from any reader. Invalid lines are an
+    /// `InvalidData` error wrapping a `MountInfoError`: the kernel
+    /// never writes them, so we don't guess.
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<MountTable> {
+        let mut mounts = vec![];
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mount = parse_mountinfo_line(&line).map_err(|reason| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    MountInfoError {
+                        line: idx + 1,
+                        raw: line.clone(),
+                        reason,
+                    },
+                )
+            })?;
+            mounts.push(mount);
+        }
+        Ok(MountTable { mounts })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<MountTable> {
+        MountTable::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// The mounts visible to this process.
+    pub fn read_self() -> io::Result<MountTable> {
+        MountTable::from_path("/proc/self/mountinfo")
+    }
+
+    /// The mount currently visible at `mount_point`: the last one
+    /// mounted there, since it hides any earlier ones.
+    pub fn find(&self, mount_point: &str) -> Option<&MountInfo> {
+        self.mounts.iter().rev().find(|mount| mount.mount_point == mount_point)
+    }
+
+    pub fn is_mounted(&self, mount_point: &str) -> bool {
+        self.find(mount_point).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Based on util-linux 2.32.1's tests/ts/libmount/files/mountinfo,
+    // plus the example from the kernel's proc documentation and mounts
+    // with escaped names.
+    const MOUNTINFO: &str = "15 20 0:3 / /proc rw,relatime - proc /proc rw
+16 20 0:15 / /sys rw,relatime - sysfs /sys rw
+17 20 0:5 / /dev rw,relatime - devtmpfs udev rw,size=1983516k,nr_inodes=495879,mode=755
+18 17 0:10 / /dev/pts rw,relatime - devpts devpts rw,gid=5,mode=620,ptmxmode=000
+19 17 0:16 / /dev/shm rw,relatime - tmpfs tmpfs rw
+20 1 8:3 / / rw,noatime shared:1 - ext3 /dev/sda3 rw,errors=continue,user_xattr,acl,barrier=0,data=ordered
+36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+40 20 8:5 / /mnt/My\\040Disk rw,relatime shared:7 master:2 - ext4 /dev/sda5 rw
+41 20 0:40 / /mnt/remote rw,relatime - nfs4 foo.com:/mnt/share rw,vers=4.2
+42 20 0:41 / /mnt/nosuper rw - autofs systemd-1
+";
+
+    #[test]
+    fn parse_line_documentation_example() {
+        assert_eq!(
+            parse_mountinfo_line(
+                "36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue"
+            ),
+            Ok(MountInfo {
+                mount_id: 36,
+                parent_id: 35,
+                major: 98,
+                minor: 0,
+                root: "/mnt1".to_string(),
+                mount_point: "/mnt2".to_string(),
+                mount_options: MountOptions::parse("rw,noatime"),
+                propagation: vec![Propagation::Master(1)],
+                fs_type: "ext3".to_string(),
+                source: "/dev/root".to_string(),
+                super_options: MountOptions::parse("rw,errors=continue"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_line_escapes_and_multiple_optional_fields() {
+        let mount = parse_mountinfo_line(
+            "40 20 8:5 /a\\134b /mnt/My\\040Disk rw shared:7 master:2 propagate_from:3 unbindable x:y - ext4 /dev/disk\\011x rw"
+        ).unwrap();
+        assert_eq!(mount.root, "/a\\b");
+        assert_eq!(mount.mount_point, "/mnt/My Disk");
+        assert_eq!(mount.source, "/dev/disk\tx");
+        assert_eq!(
+            mount.propagation,
+            vec![
+                Propagation::Shared(7),
+                Propagation::Master(2),
+                Propagation::PropagateFrom(3),
+                Propagation::Unbindable,
+                Propagation::Other("x:y".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_line_errors() {
+        assert_eq!(
+            parse_mountinfo_line("15 20 0:3 / /proc"),
+            Err(MountInfoErrorReason::TooFewFields)
+        );
+        assert_eq!(
+            parse_mountinfo_line("x 20 0:3 / /proc rw - proc proc rw"),
+            Err(MountInfoErrorReason::InvalidId)
+        );
+        assert_eq!(
+            parse_mountinfo_line("15 20 03 / /proc rw - proc proc rw"),
+            Err(MountInfoErrorReason::InvalidDevice)
+        );
+        assert_eq!(
+            parse_mountinfo_line("15 20 0:3 / /proc rw shared:1"),
+            Err(MountInfoErrorReason::MissingSeparator)
+        );
+        assert_eq!(
+            parse_mountinfo_line("15 20 0:3 / /proc\\377 rw - proc proc rw"),
+            Err(MountInfoErrorReason::BadEscape)
+        );
+    }
+
+    #[test]
+    fn read_table() {
+        let table = MountTable::from_reader(MOUNTINFO.as_bytes()).unwrap();
+        assert_eq!(table.mounts.len(), 10);
+        assert_eq!(table.find("/").unwrap().source, "/dev/sda3");
+        assert_eq!(table.find("/").unwrap().propagation, vec![Propagation::Shared(1)]);
+        assert_eq!(table.find("/dev/pts").unwrap().super_options.get("gid"), Some("5"));
+        assert_eq!(table.find("/mnt/remote").unwrap().source, "foo.com:/mnt/share");
+        assert!(table.find("/mnt/nosuper").unwrap().super_options.is_empty());
+        assert!(table.is_mounted("/mnt/My Disk"));
+        assert!(!table.is_mounted("/home"));
+    }
+
+    #[test]
+    fn find_returns_topmost_mount() {
+        let table = MountTable::from_reader(
+            "20 1 8:3 / /mnt rw - ext4 /dev/sda3 rw\n21 20 0:30 / /mnt rw - tmpfs tmpfs rw\n".as_bytes()
+        ).unwrap();
+        assert_eq!(table.find("/mnt").unwrap().fs_type, "tmpfs");
+    }
+
+    #[test]
+    fn read_table_error() {
+        let err = MountTable::from_reader("15 20 0:3 / /proc rw - proc /proc rw\nbroken\n".as_bytes())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "mountinfo line 2: mount ID is not a number: \"broken\"");
+    }
+}
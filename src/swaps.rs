@@ -0,0 +1,223 @@
+//! Active swap devices, as reported by /proc/swaps.
+//!
+//! ```text
+//! Filename                                Type            Size    Used    Priority
+//! /dev/sda2                               partition       8388604 0       -2
+//! ```
+
+use device::DeviceResolver;
+use fstab::{FSTabEntry, FSTabFile};
+use mangle::unmangle;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::ptr;
+
+/// A swap area the kernel is currently using
+#[derive(Debug, PartialEq, Clone)]
+pub struct Swap {
+    /// The device or file, as the kernel names it. Octal escapes are
+    /// decoded.
+    pub filename: String,
+
+    /// `partition` or `file`.
+    pub swap_type: String,
+
+    /// Size in KiB.
+    pub size: u64,
+
+    /// KiB in use.
+    pub used: u64,
+
+    /// Higher priorities are used first. Negative priorities are
+    /// assigned by the kernel when none was given.
+    pub priority: i32,
+}
+
+/// Parse a single line of /proc/swaps. Returns None for the header and
+/// for invalid lines.
+pub fn parse_swaps_line(line: &str) -> Option<Swap> {
+    let mut fields = line.split_whitespace();
+    let swap = Swap {
+        filename: unmangle(fields.next()?)?.into_owned(),
+        swap_type: fields.next()?.to_string(),
+        size: fields.next()?.parse().ok()?,
+        used: fields.next()?.parse().ok()?,
+        priority: fields.next()?.parse().ok()?,
+    };
+    if fields.next().is_none() {
+        Some(swap)
+    } else {
+        None
+    }
+}
+
+/// How the swap entries of an fstab line up with the active swaps
+#[derive(Debug, PartialEq)]
+pub struct SwapMatches<'a, 'b> {
+    /// fstab entries which are already in use, with their swap.
+    pub active: Vec<(&'a FSTabEntry<'b>, &'a Swap)>,
+
+    /// fstab entries which aren't in use and need `swapon`.
+    pub inactive: Vec<&'a FSTabEntry<'b>>,
+
+    /// Active swaps which no fstab entry refers to, which are
+    /// candidates for `swapoff`.
+    pub unlisted: Vec<&'a Swap>,
+}
+
+/// Every active swap area
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SwapTable {
+    pub swaps: Vec<Swap>,
+}
+
+impl SwapTable {
+    /// Parse /proc/swaps from any reader. The header is skipped and an
+    /// invalid line is an `InvalidData` error.
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<SwapTable> {
+        let mut swaps = vec![];
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() || (idx == 0 && line.starts_with("Filename")) {
+                continue;
+            }
+            match parse_swaps_line(&line) {
+                Some(swap) => swaps.push(swap),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("swaps line {}: invalid: {:?}", idx + 1, line),
+                    ))
+                }
+            }
+        }
+        Ok(SwapTable { swaps })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<SwapTable> {
+        SwapTable::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// The swaps this system is using.
+    pub fn read_self() -> io::Result<SwapTable> {
+        SwapTable::from_path("/proc/swaps")
+    }
+
+    /// The active swap an fstab entry refers to.
+    ///
+    /// The kernel reports the canonical device node, so the entry's
+    /// spec is resolved first: `UUID=...` and /dev/disk/by-uuid paths
+    /// match the `/dev/sda2` they point at. Swap files and sources
+    /// which can't be resolved are compared as written.
+    pub fn find_entry(&self, entry: &FSTabEntry, resolver: &DeviceResolver) -> Option<&Swap> {
+        let resolved = resolver.resolve(&entry.source()).ok();
+        self.swaps.iter().find(|swap| {
+            let filename = Path::new(&swap.filename);
+            resolved.as_deref() == Some(filename)
+                || Path::new(&*entry.spec) == filename
+        })
+    }
+
+    /// Match every swap entry of an fstab against the active swaps.
+    pub fn match_fstab<'a, 'b>(&'a self, fstab: &'a FSTabFile<'b>, resolver: &DeviceResolver) -> SwapMatches<'a, 'b> {
+        let mut matches = SwapMatches {
+            active: vec![],
+            inactive: vec![],
+            unlisted: vec![],
+        };
+
+        for entry in fstab.entries.iter().filter(|entry| entry.is_swap()) {
+            match self.find_entry(entry, resolver) {
+                Some(swap) => matches.active.push((entry, swap)),
+                None => matches.inactive.push(entry),
+            }
+        }
+
+        matches.unlisted = self
+            .swaps
+            .iter()
+            .filter(|swap| !matches.active.iter().any(|&(_, active)| ptr::eq(active, *swap)))
+            .collect();
+
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::parse_fstab;
+    use test_util::TempDir;
+
+    const SWAPS: &str = "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority
+/dev/sda2                               partition\t8388604\t\t1024\t\t-2
+/swap\\040file                           file\t\t2097148\t\t0\t\t10
+/dev/zram0                              partition\t4194300\t\t0\t\t100
+";
+
+    #[test]
+    fn parse_line() {
+        assert_eq!(
+            parse_swaps_line("/dev/sda2 partition 8388604 1024 -2"),
+            Some(Swap {
+                filename: "/dev/sda2".to_string(),
+                swap_type: "partition".to_string(),
+                size: 8388604,
+                used: 1024,
+                priority: -2,
+            })
+        );
+        assert_eq!(parse_swaps_line("Filename Type Size Used Priority"), None);
+        assert_eq!(parse_swaps_line("/dev/sda2 partition 8388604 1024"), None);
+        assert_eq!(parse_swaps_line("/dev/sda2 partition 8388604 1024 -2 x"), None);
+    }
+
+    #[test]
+    fn read_table() {
+        let table = SwapTable::from_reader(SWAPS.as_bytes()).unwrap();
+        assert_eq!(table.swaps.len(), 3);
+        assert_eq!(table.swaps[1].filename, "/swap file");
+        assert_eq!(table.swaps[1].swap_type, "file");
+        assert_eq!(table.swaps[2].priority, 100);
+    }
+
+    #[test]
+    fn read_table_header_only() {
+        let table = SwapTable::from_reader("Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n".as_bytes()).unwrap();
+        assert!(table.swaps.is_empty());
+    }
+
+    #[test]
+    fn read_table_error() {
+        let err = SwapTable::from_reader("Filename Type Size Used Priority\nbroken\n".as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn match_fstab_entries() {
+        let tmp = TempDir::new();
+        tmp.symlink("dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e", "../../sda2");
+        tmp.symlink("dev/disk/by-uuid/1f2aa318-9c34-462e-8d29-260819ffd657", "../../sda3");
+        let resolver = DeviceResolver::new(tmp.path().join("dev/disk"));
+
+        let table = SwapTable::from_reader(SWAPS.as_bytes()).unwrap();
+        let fstab = parse_fstab("
+/dev/disk/by-uuid/3aa72460-7d05-4bd4-861f-6ef8b82082dc / ext4 defaults 0 1
+/dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e none swap
+UUID=1f2aa318-9c34-462e-8d29-260819ffd657 swap swap defaults 0 0
+/swap\\040file none swap
+".lines());
+
+        let matches = table.match_fstab(&fstab, &resolver);
+        assert_eq!(
+            matches.active,
+            vec![
+                (&fstab.entries[1], &table.swaps[0]),
+                (&fstab.entries[3], &table.swaps[1]),
+            ]
+        );
+        assert_eq!(matches.inactive, vec![&fstab.entries[2]]);
+        assert_eq!(matches.unlisted, vec![&table.swaps[2]]);
+    }
+}
@@ -0,0 +1,121 @@
+//! systemd time spans, ie: `90s`, `5min` or `1h 30min`.
+//!
+//! See `man 7 systemd.time` and `parse_sec` in systemd's
+//! src/basic/time-util.c.
+
+use std::time::Duration;
+
+/// Units and their length in microseconds, longest names first so
+/// `ms` isn't read as `m` followed by garbage.
+const UNITS: &[(&str, u64)] = &[
+    ("seconds", 1_000_000),
+    ("second", 1_000_000),
+    ("minutes", 60_000_000),
+    ("minute", 60_000_000),
+    ("months", 2_629_800_000_000),
+    ("month", 2_629_800_000_000),
+    ("hours", 3_600_000_000),
+    ("hour", 3_600_000_000),
+    ("days", 86_400_000_000),
+    ("day", 86_400_000_000),
+    ("weeks", 604_800_000_000),
+    ("week", 604_800_000_000),
+    ("years", 31_557_600_000_000),
+    ("year", 31_557_600_000_000),
+    ("msec", 1_000),
+    ("usec", 1),
+    ("sec", 1_000_000),
+    ("min", 60_000_000),
+    ("hr", 3_600_000_000),
+    ("ms", 1_000),
+    ("us", 1),
+    ("µs", 1),
+    ("s", 1_000_000),
+    ("m", 60_000_000),
+    ("h", 3_600_000_000),
+    ("d", 86_400_000_000),
+    ("w", 604_800_000_000),
+    ("M", 2_629_800_000_000),
+    ("y", 31_557_600_000_000),
+];
+
+/// Parse a time span. A number without a unit is seconds, and
+/// `infinity` is `Duration::MAX`. Returns None if the span is invalid.
+pub fn parse_timespan(span: &str) -> Option<Duration> {
+    let span = span.trim();
+    if span == "infinity" {
+        return Some(Duration::MAX);
+    }
+    if span.is_empty() {
+        return None;
+    }
+
+    let mut total: u64 = 0;
+    let mut rest = span;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        if number_len == 0 {
+            return None;
+        }
+        let number = rest[..number_len].parse::<f64>().ok()?;
+        rest = rest[number_len..].trim_start();
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        let multiplier = if unit.is_empty() {
+            1_000_000
+        } else {
+            UNITS.iter().find(|&&(name, _)| name == unit)?.1
+        };
+        rest = rest[unit_len..].trim_start();
+
+        total = total.checked_add((number * multiplier as f64) as u64)?;
+    }
+
+    Some(Duration::from_micros(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plain_seconds() {
+        assert_eq!(parse_timespan("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timespan("0"), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(parse_timespan("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timespan("5min"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_timespan("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_timespan("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_timespan("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_timespan("1 week"), Some(Duration::from_secs(604_800)));
+    }
+
+    #[test]
+    fn parse_combined() {
+        assert_eq!(parse_timespan("1h 30min"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_timespan("1min30s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timespan(" 2 min 5 s "), Some(Duration::from_secs(125)));
+    }
+
+    #[test]
+    fn parse_infinity() {
+        assert_eq!(parse_timespan("infinity"), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse_timespan(""), None);
+        assert_eq!(parse_timespan("s"), None);
+        assert_eq!(parse_timespan("5 fortnights"), None);
+        assert_eq!(parse_timespan("-5s"), None);
+    }
+}
@@ -0,0 +1,331 @@
+//! systemd unit files.
+//!
+//! ```text
+//! [Unit]
+//! Description=Example
+//! After=network.target
+//!
+//! [Service]
+//! ExecStart=/bin/example \
+//!     --verbose
+//! ```
+//!
+//! This is the INI dialect of systemd.syntax(7) as parsed by
+//! `config_parse` in systemd's src/shared/conf-parser.c. Values are kept
+//! as written: specifiers and quoting are not interpreted.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub mod compare;
+pub mod load;
+pub mod plan;
+
+pub use self::compare::{compare, ChangedKey, KeyEffect, UnitChange};
+pub use self::load::{EffectiveUnit, LoadedUnit, UnitDir};
+pub use self::plan::{plan, UnitActions};
+
+/// The keys of one section, each with every value assigned to it
+pub type Section = BTreeMap<String, Vec<String>>;
+
+/// Why a unit file could not be parsed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UnitFileErrorReason {
+    /// A line starting with `[` doesn't end with `]`.
+    InvalidSectionHeader,
+
+    /// An assignment came before any section header.
+    OutsideSection,
+
+    /// A line is neither a section header, comment nor `Key=Value`.
+    MissingEquals,
+
+    /// The key of an assignment is empty.
+    EmptyKey,
+}
+
+impl fmt::Display for UnitFileErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            UnitFileErrorReason::InvalidSectionHeader => "invalid section header",
+            UnitFileErrorReason::OutsideSection => "assignment outside of a section",
+            UnitFileErrorReason::MissingEquals => "missing '='",
+            UnitFileErrorReason::EmptyKey => "empty key",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// A line which could not be parsed, and why
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnitFileError {
+    /// The 1-based line number. For a line continued with `\`, the
+    /// line it started on.
+    pub line: usize,
+
+    /// The line, joined with its continuations.
+    pub raw: String,
+
+    pub reason: UnitFileErrorReason,
+}
+
+impl fmt::Display for UnitFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}: {:?}", self.line, self.reason, self.raw)
+    }
+}
+
+impl Error for UnitFileError {}
+
+/// Is this line a comment? Also used inside continuations, where
+/// systemd skips comment lines too.
+fn is_comment(line: &str) -> bool {
+    line.starts_with('#') || line.starts_with(';')
+}
+
+/// Parse a boolean the way systemd's `parse_boolean` does.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match &value.to_ascii_lowercase()[..] {
+        "1" | "yes" | "y" | "true" | "t" | "on" => Some(true),
+        "0" | "no" | "n" | "false" | "f" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// A parsed unit file, or a unit file and its drop-ins
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct UnitFile {
+    /// Sections by name. A section which appears several times is
+    /// merged into one.
+    pub sections: BTreeMap<String, Section>,
+}
+
+impl UnitFile {
+    pub fn parse(text: &str) -> Result<UnitFile, UnitFileError> {
+        let mut unit = UnitFile::default();
+        unit.update(text)?;
+        Ok(unit)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<UnitFile> {
+        let text = fs::read_to_string(path)?;
+        UnitFile::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Parse `text` on top of this unit, as systemd applies a drop-in.
+    ///
+    /// Each assignment adds a value to its key, and an empty assignment
+    /// like `After=` removes the values assigned so far. On error the
+    /// lines before the invalid one have already been applied.
+    pub fn update(&mut self, text: &str) -> Result<(), UnitFileError> {
+        let mut section: Option<String> = None;
+        let mut lines = text.lines().enumerate();
+
+        while let Some((idx, line)) = lines.next() {
+            let mut line = line.trim().to_string();
+            if line.is_empty() || is_comment(&line) {
+                continue;
+            }
+
+            while line.ends_with('\\') {
+                line.pop();
+                line.push(' ');
+                match lines.by_ref().map(|(_, next)| next.trim()).find(|next| !is_comment(next)) {
+                    Some(next) => line.push_str(next),
+                    None => break,
+                }
+            }
+
+            let error = |reason| UnitFileError {
+                line: idx + 1,
+                raw: line.clone(),
+                reason,
+            };
+
+            if line.starts_with('[') {
+                if !line.ends_with(']') || line.len() < 3 {
+                    return Err(error(UnitFileErrorReason::InvalidSectionHeader));
+                }
+                let name = line[1..line.len() - 1].to_string();
+                self.sections.entry(name.clone()).or_default();
+                section = Some(name);
+                continue;
+            }
+
+            let name = match section {
+                Some(ref name) => name,
+                None => return Err(error(UnitFileErrorReason::OutsideSection)),
+            };
+            let equals = line.find('=').ok_or_else(|| error(UnitFileErrorReason::MissingEquals))?;
+            let key = line[..equals].trim();
+            let value = line[equals + 1..].trim();
+            if key.is_empty() {
+                return Err(error(UnitFileErrorReason::EmptyKey));
+            }
+
+            let values = self.sections
+                .get_mut(name)
+                .expect("section headers create their section")
+                .entry(key.to_string())
+                .or_default();
+            if value.is_empty() {
+                values.clear();
+            } else {
+                values.push(value.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn section(&self, section: &str) -> Option<&Section> {
+        self.sections.get(section)
+    }
+
+    /// Every value assigned to a key since it was last reset, in order.
+    pub fn values(&self, section: &str, key: &str) -> &[String] {
+        self.sections
+            .get(section)
+            .and_then(|keys| keys.get(key))
+            .map(|values| &values[..])
+            .unwrap_or(&[])
+    }
+
+    /// The value of a single valued key: the last one assigned.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.values(section, key).last().map(|value| &value[..])
+    }
+
+    /// A boolean key. None if it is unset or isn't a boolean.
+    pub fn get_bool(&self, section: &str, key: &str) -> Option<bool> {
+        self.get(section, key).and_then(parse_bool)
+    }
+
+    /// A list key like `After=`, whose assignments each add one or
+    /// more whitespace separated items.
+    pub fn get_list(&self, section: &str, key: &str) -> Vec<&str> {
+        self.values(section, key)
+            .iter()
+            .flat_map(|value| value.split_whitespace())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::TempDir;
+
+    const SERVICE: &str = "# A comment
+; another comment
+[Unit]
+Description = Example service
+After=network.target
+After=local-fs.target   remote-fs.target
+Wants=a.service
+
+[Service]
+Type=simple
+ExecStart=/bin/example \\
+    --verbose \\
+    # not part of the command
+    --color=auto
+Environment=A=1
+RemainAfterExit=yes
+PrivateTmp=off
+
+[Unit]
+Wants=
+X-RestartIfChanged=false
+";
+
+    #[test]
+    fn parse() {
+        let unit = UnitFile::parse(SERVICE).unwrap();
+        assert_eq!(unit.sections.keys().collect::<Vec<_>>(), vec!["Service", "Unit"]);
+        assert_eq!(unit.get("Unit", "Description"), Some("Example service"));
+        assert_eq!(unit.get("Service", "ExecStart"), Some("/bin/example  --verbose  --color=auto"));
+        assert_eq!(unit.get("Service", "Environment"), Some("A=1"));
+        assert_eq!(unit.get("Service", "Missing"), None);
+        assert_eq!(unit.get("Missing", "Type"), None);
+        assert!(unit.section("Install").is_none());
+    }
+
+    #[test]
+    fn lists_and_resets() {
+        let unit = UnitFile::parse(SERVICE).unwrap();
+        assert_eq!(unit.values("Unit", "After"), &["network.target", "local-fs.target   remote-fs.target"]);
+        assert_eq!(
+            unit.get_list("Unit", "After"),
+            vec!["network.target", "local-fs.target", "remote-fs.target"]
+        );
+        assert!(unit.values("Unit", "Wants").is_empty());
+        assert!(unit.get_list("Unit", "Missing").is_empty());
+    }
+
+    #[test]
+    fn booleans() {
+        let unit = UnitFile::parse(SERVICE).unwrap();
+        assert_eq!(unit.get_bool("Service", "RemainAfterExit"), Some(true));
+        assert_eq!(unit.get_bool("Service", "PrivateTmp"), Some(false));
+        assert_eq!(unit.get_bool("Unit", "X-RestartIfChanged"), Some(false));
+        assert_eq!(unit.get_bool("Service", "Type"), None);
+        assert_eq!(unit.get_bool("Service", "Missing"), None);
+        for value in &["1", "yes", "Y", "true", "t", "ON"] {
+            assert_eq!(parse_bool(value), Some(true));
+        }
+        for value in &["0", "no", "n", "FALSE", "f", "off"] {
+            assert_eq!(parse_bool(value), Some(false));
+        }
+        assert_eq!(parse_bool("maybe"), None);
+    }
+
+    #[test]
+    fn update_like_a_drop_in() {
+        let mut unit = UnitFile::parse(SERVICE).unwrap();
+        unit.update("[Unit]\nAfter=\nAfter=x.target\n[Service]\nType=oneshot\n").unwrap();
+        assert_eq!(unit.get_list("Unit", "After"), vec!["x.target"]);
+        assert_eq!(unit.values("Service", "Type"), &["simple", "oneshot"]);
+        assert_eq!(unit.get("Service", "Type"), Some("oneshot"));
+    }
+
+    #[test]
+    fn trailing_continuation() {
+        let unit = UnitFile::parse("[Service]\nExecStart=/bin/true \\\n").unwrap();
+        assert_eq!(unit.get("Service", "ExecStart"), Some("/bin/true"));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text| UnitFile::parse(text).unwrap_err();
+        assert_eq!(
+            error("Key=value\n"),
+            UnitFileError {
+                line: 1,
+                raw: "Key=value".to_string(),
+                reason: UnitFileErrorReason::OutsideSection,
+            }
+        );
+        assert_eq!(error("[Unit\n").reason, UnitFileErrorReason::InvalidSectionHeader);
+        assert_eq!(error("[]\n").reason, UnitFileErrorReason::InvalidSectionHeader);
+        assert_eq!(error("[Unit]\n=value\n").reason, UnitFileErrorReason::EmptyKey);
+
+        let err = error("[Unit]\n\nDescription=ok\nbroken \\\n  line\n");
+        assert_eq!(err.line, 4);
+        assert_eq!(err.to_string(), "line 4: missing '=': \"broken  line\"");
+    }
+
+    #[test]
+    fn from_path() {
+        let dir = TempDir::new();
+        let path = dir.write("example.service", SERVICE);
+        assert_eq!(UnitFile::from_path(&path).unwrap(), UnitFile::parse(SERVICE).unwrap());
+
+        let broken = dir.write("broken.service", "oops\n");
+        assert_eq!(UnitFile::from_path(&broken).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,225 @@
+//! Deciding whether a change to a unit needs a restart.
+//!
+//! Some keys don't affect a running unit: descriptions, `[Install]`
+//! which is only read by `systemctl enable`, and NixOS's own `X-` keys.
+//! Restarting a unit because only those changed is needless downtime.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use unit_file::UnitFile;
+
+/// Sections which never need a restart.
+pub const IGNORED_SECTIONS: &[&str] = &["Install"];
+
+/// What a change to a key takes to apply
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum KeyEffect {
+    /// Nothing: the running unit doesn't depend on it.
+    Ignored,
+
+    /// A reload.
+    Reload,
+
+    /// A restart.
+    Restart,
+}
+
+/// The effect of changing a key, as `(section, key, effect)`. Keys not
+/// listed need a restart, except for those starting with `X-`, which
+/// are ignored.
+///
+/// NixOS sets `X-Reload-Triggers` from `reloadTriggers` and
+/// `X-Restart-Triggers` from `restartTriggers`.
+pub const KEY_EFFECTS: &[(&str, &str, KeyEffect)] = &[
+    ("Unit", "Description", KeyEffect::Ignored),
+    ("Unit", "Documentation", KeyEffect::Ignored),
+    ("Unit", "OnFailure", KeyEffect::Ignored),
+    ("Unit", "OnSuccess", KeyEffect::Ignored),
+    ("Unit", "OnFailureJobMode", KeyEffect::Ignored),
+    ("Unit", "IgnoreOnIsolate", KeyEffect::Ignored),
+    ("Unit", "StopWhenUnneeded", KeyEffect::Ignored),
+    ("Unit", "RefuseManualStart", KeyEffect::Ignored),
+    ("Unit", "RefuseManualStop", KeyEffect::Ignored),
+    ("Unit", "AllowIsolate", KeyEffect::Ignored),
+    ("Unit", "CollectMode", KeyEffect::Ignored),
+    ("Unit", "SourcePath", KeyEffect::Ignored),
+    ("Unit", "X-Reload-Triggers", KeyEffect::Reload),
+    ("Unit", "X-Restart-Triggers", KeyEffect::Restart),
+];
+
+/// A key whose values differ between two versions of a unit
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct ChangedKey {
+    pub section: String,
+    pub key: String,
+}
+
+impl fmt::Display for ChangedKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.section, self.key)
+    }
+}
+
+/// What a change to a unit takes to apply
+#[derive(Debug, PartialEq, Clone)]
+pub enum UnitChange {
+    /// Nothing changed.
+    Unchanged,
+
+    /// Only ignored or reload keys changed. `reload` is whether any of
+    /// them was a reload key.
+    ReloadSafe { reload: bool },
+
+    /// The unit has to be restarted because of `keys`, in order.
+    Restart { keys: Vec<ChangedKey> },
+}
+
+/// What a change to `key` in `section` takes to apply.
+pub fn key_effect(section: &str, key: &str) -> KeyEffect {
+    if IGNORED_SECTIONS.contains(&section) {
+        return KeyEffect::Ignored;
+    }
+    let listed = KEY_EFFECTS
+        .iter()
+        .find(|&&(listed_section, listed_key, _)| listed_section == section && listed_key == key);
+    match listed {
+        Some(&(_, _, effect)) => effect,
+        None if key.starts_with("X-") => KeyEffect::Ignored,
+        None => KeyEffect::Restart,
+    }
+}
+
+/// Every key whose values differ, sorted by section and key. A key
+/// which was reset with an empty assignment is the same as one which
+/// was never set.
+pub fn changed_keys(old: &UnitFile, new: &UnitFile) -> Vec<ChangedKey> {
+    let mut keys = BTreeSet::new();
+    for unit in &[old, new] {
+        for (section, values) in &unit.sections {
+            for key in values.keys() {
+                keys.insert((section, key));
+            }
+        }
+    }
+
+    keys.into_iter()
+        .filter(|&(section, key)| old.values(section, key) != new.values(section, key))
+        .map(|(section, key)| ChangedKey {
+            section: section.clone(),
+            key: key.clone(),
+        })
+        .collect()
+}
+
+/// Classify the change from `old` to `new`.
+pub fn compare(old: &UnitFile, new: &UnitFile) -> UnitChange {
+    let changed = changed_keys(old, new);
+    if changed.is_empty() {
+        return UnitChange::Unchanged;
+    }
+
+    let effect = |changed: &ChangedKey| key_effect(&changed.section, &changed.key);
+    let reload = changed.iter().any(|changed| effect(changed) == KeyEffect::Reload);
+    let restart = changed
+        .into_iter()
+        .filter(|changed| effect(changed) == KeyEffect::Restart)
+        .collect::<Vec<_>>();
+
+    if restart.is_empty() {
+        UnitChange::ReloadSafe { reload }
+    } else {
+        UnitChange::Restart { keys: restart }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(text: &str) -> UnitFile {
+        UnitFile::parse(text).unwrap()
+    }
+
+    fn key(section: &str, key: &str) -> ChangedKey {
+        ChangedKey {
+            section: section.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    const BASE: &str = "[Unit]
+Description=Example
+After=network.target
+X-Reload-Triggers=/nix/store/a-config
+[Service]
+ExecStart=/bin/example
+[Install]
+WantedBy=multi-user.target
+";
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(compare(&unit(BASE), &unit(BASE)), UnitChange::Unchanged);
+        assert_eq!(
+            compare(&unit("[Unit]\nAfter=a\n"), &unit("[Unit]\nAfter=a\n[Service]\nEnvironment=\n")),
+            UnitChange::Unchanged
+        );
+    }
+
+    #[test]
+    fn cosmetic() {
+        let new = BASE
+            .replace("Description=Example", "Description=Better example\nDocumentation=man:example(8)")
+            .replace("After=network.target", "After=network.target\nX-StopOnRemoval=false")
+            .replace("ExecStart=/bin/example", "ExecStart=/bin/example\nX-RestartIfChanged=false")
+            .replace("WantedBy=multi-user.target", "WantedBy=graphical.target");
+        assert_eq!(compare(&unit(BASE), &unit(&new)), UnitChange::ReloadSafe { reload: false });
+    }
+
+    #[test]
+    fn reload() {
+        let new = BASE.replace("a-config", "b-config").replace("Example", "New");
+        assert_eq!(compare(&unit(BASE), &unit(&new)), UnitChange::ReloadSafe { reload: true });
+    }
+
+    #[test]
+    fn restart_triggers() {
+        let old = unit("[Unit]\nX-Restart-Triggers=/nix/store/a-config\n[Service]\nExecStart=/bin/example\n");
+        let new = unit("[Unit]\nX-Restart-Triggers=/nix/store/b-config\n[Service]\nExecStart=/bin/example\n");
+        assert_eq!(
+            compare(&old, &new),
+            UnitChange::Restart { keys: vec![key("Unit", "X-Restart-Triggers")] }
+        );
+        assert_eq!(key_effect("Unit", "X-Restart-Triggers"), KeyEffect::Restart);
+        assert_eq!(key_effect("Unit", "X-Reload-Triggers"), KeyEffect::Reload);
+        assert_eq!(key_effect("Unit", "X-StopOnRemoval"), KeyEffect::Ignored);
+        assert_eq!(key_effect("Install", "WantedBy"), KeyEffect::Ignored);
+        assert_eq!(key_effect("Service", "ExecStart"), KeyEffect::Restart);
+    }
+
+    #[test]
+    fn restart_reports_keys() {
+        let new = BASE
+            .replace("/bin/example", "/bin/example --verbose")
+            .replace("After=network.target", "")
+            .replace("a-config", "b-config")
+            + "[Service]\nEnvironment=A=1\n";
+        assert_eq!(
+            compare(&unit(BASE), &unit(&new)),
+            UnitChange::Restart {
+                keys: vec![
+                    key("Service", "Environment"),
+                    key("Service", "ExecStart"),
+                    key("Unit", "After"),
+                ],
+            }
+        );
+        assert_eq!(key("Service", "ExecStart").to_string(), "[Service] ExecStart");
+    }
+
+    #[test]
+    fn changed_keys_includes_ignored() {
+        let new = BASE.replace("Example", "New");
+        assert_eq!(changed_keys(&unit(BASE), &unit(&new)), vec![key("Unit", "Description")]);
+    }
+}
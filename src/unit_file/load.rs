@@ -0,0 +1,353 @@
+//! Loading a unit with its drop-ins from a unit directory.
+//!
+//! A unit's effective configuration is its unit file with every
+//! `.conf` file from its drop-in directories applied on top. For
+//! `foo-bar@baz.service` the drop-in directories are, most specific
+//! first:
+//!
+//! ```text
+//! foo-bar@baz.service.d/
+//! foo-bar@.service.d/
+//! foo-.service.d/
+//! service.d/
+//! ```
+//!
+//! Drop-ins with the same file name override each other, with the most
+//! specific directory winning. The rest are applied in order of their
+//! file names, no matter which directory they came from. See
+//! `unit_file_find_dropin_paths` in systemd's src/shared/dropin.c and
+//! systemd.unit(5).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use unit_file::UnitFile;
+
+/// The suffixes of the unit types which can have unit files.
+pub const UNIT_SUFFIXES: &[&str] = &[
+    "service", "socket", "device", "mount", "automount", "swap", "target", "path", "timer",
+    "slice", "scope",
+];
+
+/// A unit as systemd would load it
+#[derive(Debug, PartialEq, Clone)]
+pub enum LoadedUnit {
+    /// The unit file is a symlink to /dev/null, or empty.
+    Masked,
+
+    Found(EffectiveUnit),
+}
+
+/// A unit file merged with its drop-ins
+#[derive(Debug, PartialEq, Clone)]
+pub struct EffectiveUnit {
+    pub unit: UnitFile,
+
+    /// The unit file which was loaded: the unit's own, or its
+    /// template's for an instance without one.
+    pub fragment: PathBuf,
+
+    /// The drop-ins applied on top of the fragment, in order.
+    pub drop_ins: Vec<PathBuf>,
+}
+
+/// Is this path, after following symlinks, /dev/null?
+fn is_masked(path: &Path) -> bool {
+    fs::canonicalize(path).map(|target| target == Path::new("/dev/null")).unwrap_or(false)
+}
+
+/// Is this path, after following symlinks, an empty file? systemd
+/// treats an empty unit file as masked, but not an empty drop-in.
+fn is_empty(path: &Path) -> bool {
+    fs::metadata(path).map(|metadata| metadata.is_file() && metadata.len() == 0).unwrap_or(false)
+}
+
+/// Does anything, even a dangling symlink, exist at this path?
+fn exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// The template a unit instance belongs to: `foo@bar.service` is an
+/// instance of `foo@.service`.
+pub fn template_name(name: &str) -> Option<String> {
+    let at = name.find('@')?;
+    let dot = name.rfind('.')?;
+    if dot < at || dot == at + 1 {
+        return None;
+    }
+    Some(format!("{}{}", &name[..at + 1], &name[dot..]))
+}
+
+/// The drop-in directory names for a unit, most specific first.
+pub fn drop_in_dirs(name: &str) -> Vec<String> {
+    let dot = match name.rfind('.') {
+        Some(dot) => dot,
+        None => return vec![format!("{}.d", name)],
+    };
+    let suffix = &name[dot..];
+
+    let mut dirs = vec![format!("{}.d", name)];
+    if let Some(template) = template_name(name) {
+        dirs.push(format!("{}.d", template));
+    }
+
+    // Every prefix ending in a dash, longest first. A prefix stops at
+    // the instance, so `a-b@c-d.service` has only `a-.service.d`.
+    let stem = &name[..dot];
+    let stem = &stem[..stem.find('@').unwrap_or(stem.len())];
+    for (idx, _) in stem.rmatch_indices('-') {
+        dirs.push(format!("{}{}.d", &stem[..idx + 1], suffix));
+    }
+
+    dirs.push(format!("{}.d", &suffix[1..]));
+    dirs
+}
+
+/// Loads units from one directory, like a toplevel's
+/// etc/systemd/system
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitDir {
+    root: PathBuf,
+}
+
+impl UnitDir {
+    pub fn new<P: Into<PathBuf>>(root: P) -> UnitDir {
+        UnitDir { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The names of the unit files in the directory, sorted. Drop-in
+    /// and `.wants` directories are not included, but templates are.
+    pub fn unit_names(&self) -> io::Result<Vec<String>> {
+        let mut names = vec![];
+        for dir_entry in fs::read_dir(&self.root)? {
+            let name = match dir_entry?.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            let is_unit = name.rfind('.').is_some_and(|dot| {
+                dot > 0 && UNIT_SUFFIXES.contains(&&name[dot + 1..])
+            });
+            if is_unit {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Load a unit and its drop-ins. None if it has no unit file, not
+    /// even through its template.
+    ///
+    /// Symlinks are followed, so a unit directory pointing into
+    /// /nix/store works. A unit file or drop-in which is a symlink to
+    /// /dev/null is masked: a masked drop-in also hides any less
+    /// specific drop-in with the same name. An empty unit file is
+    /// masked too.
+    pub fn load(&self, name: &str) -> io::Result<Option<LoadedUnit>> {
+        let mut fragment = self.root.join(name);
+        if !exists(&fragment) {
+            match template_name(name) {
+                Some(template) if exists(&self.root.join(&template)) => {
+                    fragment = self.root.join(template);
+                }
+                _ => return Ok(None),
+            }
+        }
+        if is_masked(&fragment) || is_empty(&fragment) {
+            return Ok(Some(LoadedUnit::Masked));
+        }
+
+        let mut unit = UnitFile::from_path(&fragment).map_err(|err| with_path(&fragment, err))?;
+        let drop_ins = self.drop_ins(name)?;
+        for drop_in in &drop_ins {
+            let text = fs::read_to_string(drop_in).map_err(|err| with_path(drop_in, err))?;
+            unit.update(&text).map_err(|err| {
+                with_path(drop_in, io::Error::new(io::ErrorKind::InvalidData, err))
+            })?;
+        }
+
+        Ok(Some(LoadedUnit::Found(EffectiveUnit {
+            unit,
+            fragment,
+            drop_ins,
+        })))
+    }
+
+    /// The drop-ins to apply to a unit, in the order to apply them.
+    fn drop_ins(&self, name: &str) -> io::Result<Vec<PathBuf>> {
+        // File name to path. The first directory to have a name wins.
+        let mut found: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for dir in drop_in_dirs(name) {
+            let dir = self.root.join(dir);
+            let read_dir = match fs::read_dir(&dir) {
+                Ok(read_dir) => read_dir,
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(with_path(&dir, err)),
+            };
+            for dir_entry in read_dir {
+                let path = dir_entry?.path();
+                let file_name = match path.file_name().and_then(|name| name.to_str()) {
+                    Some(file_name) if file_name.ends_with(".conf") => file_name.to_string(),
+                    _ => continue,
+                };
+                found.entry(file_name).or_insert(path);
+            }
+        }
+
+        Ok(found.into_values().filter(|path| !is_masked(path)).collect())
+    }
+}
+
+/// Add the path to an error, keeping its kind.
+fn with_path(path: &Path, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::TempDir;
+
+    #[test]
+    fn template_names() {
+        assert_eq!(template_name("getty@tty1.service"), Some("getty@.service".to_string()));
+        assert_eq!(template_name("getty@.service"), None);
+        assert_eq!(template_name("sshd.service"), None);
+    }
+
+    #[test]
+    fn drop_in_dir_order() {
+        assert_eq!(
+            drop_in_dirs("foo-bar@baz-1.service"),
+            vec![
+                "foo-bar@baz-1.service.d",
+                "foo-bar@.service.d",
+                "foo-.service.d",
+                "service.d",
+            ]
+        );
+        assert_eq!(drop_in_dirs("sshd.service"), vec!["sshd.service.d", "service.d"]);
+        assert_eq!(
+            drop_in_dirs("a-b-c.mount"),
+            vec!["a-b-c.mount.d", "a-b-.mount.d", "a-.mount.d", "mount.d"]
+        );
+    }
+
+    #[test]
+    fn unit_names() {
+        let dir = TempDir::new();
+        dir.write("b.service", "[Service]\n");
+        dir.write("a.target", "[Unit]\n");
+        dir.write("getty@.service", "[Service]\n");
+        dir.write("b.service.d/override.conf", "[Service]\n");
+        dir.write("multi-user.target.wants/b.service", "[Service]\n");
+        dir.write("README", "");
+        dir.symlink("masked.service", "/dev/null");
+        assert_eq!(
+            UnitDir::new(dir.path()).unit_names().unwrap(),
+            vec!["a.target", "b.service", "getty@.service", "masked.service"]
+        );
+    }
+
+    #[test]
+    fn drop_ins_in_precedence_order() {
+        let dir = TempDir::new();
+        dir.write("foo-bar@.service", "[Service]\nExecStart=/bin/foo\nEnvironment=FROM=main\n");
+        dir.write("service.d/10-all.conf", "[Service]\nEnvironment=ALL=1\n");
+        dir.write("service.d/50-override.conf", "[Service]\nEnvironment=FROM=type\n");
+        dir.write("foo-.service.d/50-override.conf", "[Service]\nEnvironment=FROM=prefix\n");
+        dir.write("foo-bar@.service.d/20-template.conf", "[Service]\nEnvironment=TEMPLATE=1\n");
+        dir.write("foo-bar@one.service.d/30-instance.conf", "[Service]\nEnvironment=\nEnvironment=INSTANCE=1\n");
+        dir.write("foo-bar@one.service.d/not-a-drop-in", "garbage");
+
+        let units = UnitDir::new(dir.path());
+        let one = match units.load("foo-bar@one.service").unwrap() {
+            Some(LoadedUnit::Found(unit)) => unit,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(one.fragment, dir.path().join("foo-bar@.service"));
+        assert_eq!(
+            one.drop_ins,
+            vec![
+                dir.path().join("service.d/10-all.conf"),
+                dir.path().join("foo-bar@.service.d/20-template.conf"),
+                dir.path().join("foo-bar@one.service.d/30-instance.conf"),
+                dir.path().join("foo-.service.d/50-override.conf"),
+            ]
+        );
+        assert_eq!(one.unit.values("Service", "Environment"), &["INSTANCE=1", "FROM=prefix"]);
+
+        let two = match units.load("foo-bar@two.service").unwrap() {
+            Some(LoadedUnit::Found(unit)) => unit,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(
+            two.unit.values("Service", "Environment"),
+            &["FROM=main", "ALL=1", "TEMPLATE=1", "FROM=prefix"]
+        );
+
+        assert_eq!(units.load("other.service").unwrap(), None);
+    }
+
+    #[test]
+    fn follows_symlinks_and_masks() {
+        let store = TempDir::new();
+        let dir = TempDir::new();
+        let unit = store.write("unit-sshd/sshd.service", "[Service]\nExecStart=/bin/sshd\n");
+        store.write("unit-sshd-dropins/overrides.conf", "[Service]\nRestart=always\n");
+        dir.symlink("sshd.service", unit.to_str().unwrap());
+        dir.symlink("sshd.service.d", store.path().join("unit-sshd-dropins").to_str().unwrap());
+        dir.write("service.d/overrides.conf", "[Service]\nRestart=no\n");
+        dir.write("service.d/nice.conf", "[Service]\nNice=5\n");
+        dir.symlink("sshd.service.d/nice.conf", "/dev/null");
+        dir.symlink("masked.service", "/dev/null");
+        dir.write("masked.service.d/overrides.conf", "[Service]\n");
+
+        let units = UnitDir::new(dir.path());
+        let sshd = match units.load("sshd.service").unwrap() {
+            Some(LoadedUnit::Found(unit)) => unit,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(sshd.unit.get("Service", "ExecStart"), Some("/bin/sshd"));
+        assert_eq!(sshd.unit.get("Service", "Restart"), Some("always"));
+        assert_eq!(sshd.unit.get("Service", "Nice"), None);
+        assert_eq!(sshd.drop_ins, vec![dir.path().join("sshd.service.d/overrides.conf")]);
+
+        assert_eq!(units.load("masked.service").unwrap(), Some(LoadedUnit::Masked));
+    }
+
+    #[test]
+    fn empty_unit_file_is_masked() {
+        let dir = TempDir::new();
+        dir.write("empty.service", "");
+        dir.write("empty.service.d/overrides.conf", "[Service]\nExecStart=/bin/true\n");
+        dir.write("emptied.service", "[Service]\nExecStart=/bin/true\n");
+        dir.write("emptied.service.d/overrides.conf", "");
+
+        let units = UnitDir::new(dir.path());
+        assert_eq!(units.load("empty.service").unwrap(), Some(LoadedUnit::Masked));
+        match units.load("emptied.service").unwrap() {
+            Some(LoadedUnit::Found(unit)) => assert_eq!(unit.unit.get("Service", "ExecStart"), Some("/bin/true")),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let dir = TempDir::new();
+        dir.write("bad.service", "[Service]\nExecStart=/bin/true\n");
+        let drop_in = dir.write("bad.service.d/broken.conf", "broken\n");
+        dir.symlink("dangling.service", "/nonexistent/dangling.service");
+
+        let units = UnitDir::new(dir.path());
+        let err = units.load("bad.service").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with(&drop_in.display().to_string()));
+        assert_eq!(units.load("dangling.service").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,464 @@
+//! Deciding what to do with each active unit when switching between
+//! two systems.
+//!
+//! This follows `handleModifiedUnit` and the main loop of NixOS's
+//! switch-to-configuration.pl, but works on two unit directories and a
+//! list of active units instead of a live systemd, so the result can
+//! be checked without one.
+
+use std::collections::BTreeMap;
+use std::io;
+use unit_file::{compare, ChangedKey, LoadedUnit, UnitChange, UnitDir, UnitFile};
+
+/// Targets which can't be restarted directly.
+const UNRESTARTABLE_TARGETS: &[&str] = &[
+    "sysinit.target",
+    "basic.target",
+    "multi-user.target",
+    "graphical.target",
+];
+
+/// Targets which may still be active after a resume, and which would
+/// suspend the machine again if they were started.
+const SLEEP_TARGETS: &[&str] = &["suspend.target", "hibernate.target", "hybrid-sleep.target"];
+
+/// The units to act on to switch systems. Each list is sorted.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct UnitActions {
+    /// Units to stop before activation: removed units, changed units
+    /// which can't just be restarted, and their sockets.
+    pub stop: Vec<String>,
+
+    /// Units to start after activation: the stopped units which still
+    /// exist, and every active target but the sleep ones, which pulls
+    /// in new units.
+    pub start: Vec<String>,
+
+    /// Changed units with `X-StopIfChanged=false` and changed units
+    /// which aren't services, which are restarted after activation
+    /// instead of being stopped before it.
+    pub restart: Vec<String>,
+
+    /// Changed units with `X-ReloadIfChanged=true`, changed mount
+    /// units, which are remounted by a reload, and units where only
+    /// `X-Reload-Triggers=` changed.
+    pub reload: Vec<String>,
+
+    /// Changed units which are left alone because of
+    /// `X-RestartIfChanged=false`, `RefuseManualStop=` or
+    /// `X-OnlyManualStart=`.
+    pub skip: Vec<String>,
+
+    /// For every unit stopped or restarted because it changed, the keys
+    /// which needed it.
+    pub restart_keys: BTreeMap<String, Vec<ChangedKey>>,
+}
+
+impl UnitActions {
+    pub fn is_empty(&self) -> bool {
+        self.stop.is_empty()
+            && self.start.is_empty()
+            && self.restart.is_empty()
+            && self.reload.is_empty()
+            && self.skip.is_empty()
+            && self.restart_keys.is_empty()
+    }
+
+    fn sort(&mut self) {
+        for units in &mut [
+            &mut self.stop,
+            &mut self.start,
+            &mut self.restart,
+            &mut self.reload,
+            &mut self.skip,
+        ] {
+            units.sort();
+            units.dedup();
+        }
+    }
+}
+
+/// A `[Unit]` boolean, with the default used when it is unset.
+fn unit_bool(unit: &UnitFile, key: &str, default: bool) -> bool {
+    unit.get_bool("Unit", key).unwrap_or(default)
+}
+
+/// A `[Service]` boolean, with the default used when it is unset.
+/// NixOS writes `X-ReloadIfChanged`, `X-RestartIfChanged` and
+/// `X-StopIfChanged` here, see `serviceToUnit` in
+/// nixos/lib/systemd-lib.nix.
+fn service_bool(unit: &UnitFile, key: &str, default: bool) -> bool {
+    unit.get_bool("Service", key).unwrap_or(default)
+}
+
+/// The name without its type suffix: `sshd.service` is `sshd`.
+fn base_name(unit: &str) -> &str {
+    &unit[..unit.rfind('.').unwrap_or(unit.len())]
+}
+
+struct Planner<'a> {
+    new: &'a UnitDir,
+    active: &'a [String],
+    actions: UnitActions,
+}
+
+impl<'a> Planner<'a> {
+    fn is_active(&self, unit: &str) -> bool {
+        self.active.iter().any(|active| active == unit)
+    }
+
+    /// A unit whose configuration changed. Changed sockets are left
+    /// alone: stopping one would stop the service behind it too.
+    fn modified(&mut self, unit: &str, new: &UnitFile) -> io::Result<()> {
+        if UNRESTARTABLE_TARGETS.contains(&unit)
+            || unit.ends_with(".socket")
+            || unit.ends_with(".path")
+            || unit.ends_with(".slice")
+        {
+            return Ok(());
+        }
+
+        if unit.ends_with(".mount") {
+            self.actions.reload.push(unit.to_string());
+            return Ok(());
+        }
+
+        if service_bool(new, "X-ReloadIfChanged", false) {
+            self.actions.reload.push(unit.to_string());
+        } else if !service_bool(new, "X-RestartIfChanged", true)
+            || unit_bool(new, "RefuseManualStop", false)
+            || unit_bool(new, "X-OnlyManualStart", false)
+        {
+            self.actions.skip.push(unit.to_string());
+        } else if !service_bool(new, "X-StopIfChanged", true) || !unit.ends_with(".service") {
+            // Only services can have `ExecStop=`, so anything else is
+            // just restarted.
+            self.actions.restart.push(unit.to_string());
+        } else {
+            self.stop_and_start(unit, new)?;
+        }
+        Ok(())
+    }
+
+    /// Stop a service before activation and start it after. A socket
+    /// activated service is stopped with its sockets, and only the
+    /// sockets are started: the service starts on demand.
+    fn stop_and_start(&mut self, unit: &str, new: &UnitFile) -> io::Result<()> {
+        let mut sockets = new.get_list("Service", "Sockets")
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if sockets.is_empty() {
+            sockets.push(format!("{}.socket", base_name(unit)));
+        }
+
+        let mut socket_activated = false;
+        for socket in sockets {
+            if self.is_active(&socket) {
+                if let Some(LoadedUnit::Found(_)) = self.new.load(&socket)? {
+                    self.actions.start.push(socket.clone());
+                    socket_activated = true;
+                }
+                self.actions.stop.push(socket);
+            }
+        }
+
+        if !socket_activated {
+            self.actions.start.push(unit.to_string());
+        }
+        self.actions.stop.push(unit.to_string());
+        Ok(())
+    }
+}
+
+/// Decide what to do with each active unit to switch from the units in
+/// `old` to those in `new`.
+///
+/// Units whose only changes are the ones `compare` ignores aren't
+/// touched. Units which aren't in `old`, like systemd's own units, are
+/// left alone. New units aren't started directly: restarting the active
+/// targets pulls them in. A reload is dropped for a unit which is also
+/// being stopped or restarted.
+pub fn plan(old: &UnitDir, new: &UnitDir, active: &[String]) -> io::Result<UnitActions> {
+    let mut planner = Planner {
+        new,
+        active,
+        actions: UnitActions::default(),
+    };
+
+    let mut units = active.to_vec();
+    units.sort();
+    units.dedup();
+
+    for unit in &units {
+        let old_unit = match old.load(unit)? {
+            Some(LoadedUnit::Found(old_unit)) => old_unit,
+            _ => continue,
+        };
+
+        let new_unit = match new.load(unit)? {
+            Some(LoadedUnit::Found(new_unit)) => new_unit,
+            _ => {
+                if unit_bool(&old_unit.unit, "X-StopOnRemoval", true) {
+                    planner.actions.stop.push(unit.clone());
+                }
+                continue;
+            }
+        };
+
+        if unit.ends_with(".target") {
+            if !SLEEP_TARGETS.contains(&&unit[..])
+                && !unit_bool(&new_unit.unit, "RefuseManualStart", false)
+                && !unit_bool(&new_unit.unit, "X-OnlyManualStart", false)
+            {
+                planner.actions.start.push(unit.clone());
+            }
+            if unit_bool(&new_unit.unit, "X-StopOnReconfiguration", false) {
+                planner.actions.stop.push(unit.clone());
+            }
+        } else {
+            match compare(&old_unit.unit, &new_unit.unit) {
+                UnitChange::Unchanged | UnitChange::ReloadSafe { reload: false } => {}
+                UnitChange::ReloadSafe { reload: true } => planner.actions.reload.push(unit.clone()),
+                UnitChange::Restart { keys } => {
+                    planner.modified(unit, &new_unit.unit)?;
+                    if planner.actions.stop.contains(unit) || planner.actions.restart.contains(unit) {
+                        planner.actions.restart_keys.insert(unit.clone(), keys);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut actions = planner.actions;
+    let (stop, restart) = (actions.stop.clone(), actions.restart.clone());
+    actions.reload.retain(|unit| !stop.contains(unit) && !restart.contains(unit));
+    actions.sort();
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_util::TempDir;
+
+    /// The units of the running system.
+    const OLD: &[(&str, &str)] = &[
+        ("multi-user.target", "[Unit]\nDescription=Multi-User System\n"),
+        ("network-online.target", "[Unit]\nDescription=Network is Online\n"),
+        ("shutdown-hook.target", "[Unit]\nX-StopOnReconfiguration=true\n"),
+        ("unchanged.service", "[Service]\nExecStart=/bin/unchanged\n"),
+        ("cosmetic.service", "[Unit]\nDescription=Old\n[Service]\nExecStart=/bin/cosmetic\n"),
+        ("triggers.service", "[Unit]\nX-Reload-Triggers=/nix/store/a-conf\n[Service]\nExecStart=/bin/triggers\n"),
+        ("restart-triggers.service", "[Unit]\nX-Restart-Triggers=/nix/store/a-conf\n[Service]\nExecStart=/bin/restart-triggers\n"),
+        ("changed.service", "[Service]\nExecStart=/nix/store/old/bin/changed\n"),
+        ("drop-in-only.service", "[Service]\nExecStart=/bin/drop-in\n"),
+        ("removed.service", "[Service]\nExecStart=/bin/removed\n"),
+        ("kept-on-removal.service", "[Unit]\nX-StopOnRemoval=false\n[Service]\nExecStart=/bin/kept\n"),
+        ("masked-now.service", "[Service]\nExecStart=/bin/masked\n"),
+        ("reloadable.service", "[Service]\nExecStart=/bin/reload-old\n"),
+        ("no-restart.service", "[Service]\nExecStart=/bin/no-restart-old\n"),
+        ("manual.service", "[Service]\nExecStart=/bin/manual-old\n"),
+        ("refuse-stop.service", "[Service]\nExecStart=/bin/refuse-old\n"),
+        ("restart-in-place.service", "[Service]\nExecStart=/bin/in-place-old\n"),
+        ("socket-activated.service", "[Service]\nExecStart=/bin/socket-old\n"),
+        ("socket-activated.socket", "[Socket]\nListenStream=80\n"),
+        ("home.mount", "[Mount]\nWhat=/dev/sda2\nWhere=/home\nOptions=noatime\n"),
+        ("user.slice", "[Slice]\nCPUWeight=100\n"),
+        ("listener.socket", "[Socket]\nListenStream=8080\n"),
+        ("cleanup.timer", "[Timer]\nOnCalendar=daily\n"),
+        ("inactive.service", "[Service]\nExecStart=/bin/inactive-old\n"),
+        ("getty@.service", "[Service]\nExecStart=/bin/agetty-old %I\n"),
+    ];
+
+    /// The units of the system being switched to.
+    const NEW: &[(&str, &str)] = &[
+        ("multi-user.target", "[Unit]\nDescription=Multi-User System\n"),
+        ("network-online.target", "[Unit]\nDescription=Network is Online\nRefuseManualStart=yes\n"),
+        ("shutdown-hook.target", "[Unit]\nX-StopOnReconfiguration=true\n"),
+        ("unchanged.service", "[Service]\nExecStart=/bin/unchanged\n"),
+        ("cosmetic.service", "[Unit]\nDescription=New\n[Service]\nExecStart=/bin/cosmetic\n[Install]\nWantedBy=multi-user.target\n"),
+        ("triggers.service", "[Unit]\nX-Reload-Triggers=/nix/store/b-conf\n[Service]\nExecStart=/bin/triggers\n"),
+        ("restart-triggers.service", "[Unit]\nX-Restart-Triggers=/nix/store/b-conf\n[Service]\nExecStart=/bin/restart-triggers\n"),
+        ("changed.service", "[Service]\nExecStart=/nix/store/new/bin/changed\n"),
+        ("drop-in-only.service", "[Service]\nExecStart=/bin/drop-in\n"),
+        ("drop-in-only.service.d/overrides.conf", "[Service]\nEnvironment=DEBUG=1\n"),
+        ("reloadable.service", "[Service]\nX-ReloadIfChanged=true\nExecStart=/bin/reload-new\n"),
+        ("no-restart.service", "[Service]\nX-RestartIfChanged=false\nExecStart=/bin/no-restart-new\n"),
+        ("manual.service", "[Unit]\nX-OnlyManualStart=true\n[Service]\nExecStart=/bin/manual-new\n"),
+        ("refuse-stop.service", "[Unit]\nRefuseManualStop=yes\n[Service]\nExecStart=/bin/refuse-new\n"),
+        ("restart-in-place.service", "[Service]\nX-StopIfChanged=false\nExecStart=/bin/in-place-new\n"),
+        ("socket-activated.service", "[Service]\nExecStart=/bin/socket-new\n"),
+        ("socket-activated.socket", "[Socket]\nListenStream=80\n"),
+        ("home.mount", "[Mount]\nWhat=/dev/sda2\nWhere=/home\nOptions=noatime,nodev\n"),
+        ("user.slice", "[Slice]\nCPUWeight=200\n"),
+        ("listener.socket", "[Socket]\nListenStream=8081\n"),
+        ("cleanup.timer", "[Timer]\nOnCalendar=hourly\n"),
+        ("inactive.service", "[Service]\nExecStart=/bin/inactive-new\n"),
+        ("getty@.service", "[Service]\nExecStart=/bin/agetty-new %I\n"),
+        ("brand-new.service", "[Service]\nExecStart=/bin/new\n"),
+    ];
+
+    const ACTIVE: &[&str] = &[
+        "multi-user.target",
+        "network-online.target",
+        "shutdown-hook.target",
+        "unchanged.service",
+        "cosmetic.service",
+        "triggers.service",
+        "restart-triggers.service",
+        "changed.service",
+        "drop-in-only.service",
+        "removed.service",
+        "kept-on-removal.service",
+        "masked-now.service",
+        "reloadable.service",
+        "no-restart.service",
+        "manual.service",
+        "refuse-stop.service",
+        "restart-in-place.service",
+        "socket-activated.service",
+        "socket-activated.socket",
+        "home.mount",
+        "user.slice",
+        "listener.socket",
+        "cleanup.timer",
+        "getty@tty1.service",
+        "systemd-journald.service",
+    ];
+
+    fn unit_dir(dir: &TempDir, units: &[(&str, &str)]) -> UnitDir {
+        for &(name, contents) in units {
+            dir.write(name, contents);
+        }
+        UnitDir::new(dir.path())
+    }
+
+    fn names(units: &[&str]) -> Vec<String> {
+        units.iter().map(|unit| unit.to_string()).collect()
+    }
+
+    fn fixtures() -> (TempDir, TempDir) {
+        let old = TempDir::new();
+        let new = TempDir::new();
+        unit_dir(&old, OLD);
+        unit_dir(&new, NEW);
+        new.symlink("masked-now.service", "/dev/null");
+        (old, new)
+    }
+
+    #[test]
+    fn plan_fixtures() {
+        let (old, new) = fixtures();
+        let actions = plan(&UnitDir::new(old.path()), &UnitDir::new(new.path()), &names(ACTIVE)).unwrap();
+        assert_eq!(
+            actions,
+            UnitActions {
+                stop: names(&[
+                    "changed.service",
+                    "drop-in-only.service",
+                    "getty@tty1.service",
+                    "masked-now.service",
+                    "removed.service",
+                    "restart-triggers.service",
+                    "shutdown-hook.target",
+                    "socket-activated.service",
+                    "socket-activated.socket",
+                ]),
+                start: names(&[
+                    "changed.service",
+                    "drop-in-only.service",
+                    "getty@tty1.service",
+                    "multi-user.target",
+                    "restart-triggers.service",
+                    "shutdown-hook.target",
+                    "socket-activated.socket",
+                ]),
+                restart: names(&["cleanup.timer", "restart-in-place.service"]),
+                reload: names(&["home.mount", "reloadable.service", "triggers.service"]),
+                skip: names(&["manual.service", "no-restart.service", "refuse-stop.service"]),
+                restart_keys: actions.restart_keys.clone(),
+            }
+        );
+
+        let restart_keys = actions.restart_keys.iter()
+            .map(|(unit, keys)| (&unit[..], keys.iter().map(ToString::to_string).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            restart_keys,
+            vec![
+                ("changed.service", vec!["[Service] ExecStart".to_string()]),
+                ("cleanup.timer", vec!["[Timer] OnCalendar".to_string()]),
+                ("drop-in-only.service", vec!["[Service] Environment".to_string()]),
+                ("getty@tty1.service", vec!["[Service] ExecStart".to_string()]),
+                ("restart-in-place.service", vec!["[Service] ExecStart".to_string()]),
+                ("restart-triggers.service", vec!["[Unit] X-Restart-Triggers".to_string()]),
+                ("socket-activated.service", vec!["[Service] ExecStart".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_is_deterministic() {
+        let (old, new) = fixtures();
+        let (old, new) = (UnitDir::new(old.path()), UnitDir::new(new.path()));
+        let mut reversed = names(ACTIVE);
+        reversed.reverse();
+        reversed.push("changed.service".to_string());
+        assert_eq!(plan(&old, &new, &names(ACTIVE)).unwrap(), plan(&old, &new, &reversed).unwrap());
+    }
+
+    #[test]
+    fn same_system() {
+        let old = TempDir::new();
+        let dir = unit_dir(&old, OLD);
+        let actions = plan(&dir, &dir, &names(ACTIVE)).unwrap();
+        assert_eq!(
+            actions,
+            UnitActions {
+                stop: names(&["shutdown-hook.target"]),
+                start: names(&["multi-user.target", "network-online.target", "shutdown-hook.target"]),
+                ..UnitActions::default()
+            }
+        );
+    }
+
+    #[test]
+    fn sleep_targets() {
+        let dir = TempDir::new();
+        let units = unit_dir(&dir, &[
+            ("suspend.target", "[Unit]\nDescription=Suspend\n"),
+            ("hibernate.target", "[Unit]\nDescription=Hibernate\n"),
+            ("hybrid-sleep.target", "[Unit]\nDescription=Hybrid Suspend+Hibernate\n"),
+            ("timers.target", "[Unit]\nDescription=Timers\n"),
+        ]);
+        let active = names(&["suspend.target", "hibernate.target", "hybrid-sleep.target", "timers.target"]);
+        let actions = plan(&units, &units, &active).unwrap();
+        assert_eq!(
+            actions,
+            UnitActions {
+                start: names(&["timers.target"]),
+                ..UnitActions::default()
+            }
+        );
+    }
+
+    #[test]
+    fn explicit_sockets() {
+        let old = TempDir::new();
+        let new = TempDir::new();
+        let old = unit_dir(&old, &[
+            ("web.service", "[Service]\nExecStart=/bin/web-old\n"),
+            ("web-http.socket", "[Socket]\nListenStream=80\n"),
+            ("web-https.socket", "[Socket]\nListenStream=443\n"),
+        ]);
+        let new = unit_dir(&new, &[
+            ("web.service", "[Service]\nExecStart=/bin/web-new\nSockets=web-http.socket web-https.socket\n"),
+            ("web-http.socket", "[Socket]\nListenStream=80\n"),
+        ]);
+        let active = names(&["web.service", "web-http.socket", "web-https.socket"]);
+        let actions = plan(&old, &new, &active).unwrap();
+        assert_eq!(actions.stop, names(&["web-http.socket", "web-https.socket", "web.service"]));
+        assert_eq!(actions.start, names(&["web-http.socket"]));
+    }
+}
@@ -0,0 +1,239 @@
+//! systemd unit names for paths.
+//!
+//! Mount and swap units are named after the path they manage, escaped
+//! the way `systemd-escape --path` does it. See `unit_name_path_escape`
+//! in systemd's src/basic/unit-name.c.
+
+use fstab::{FSTabEntry, Source};
+
+/// Characters which don't need escaping, besides ASCII alphanumerics.
+/// `/` isn't included because it becomes `-`.
+fn is_valid_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b':' || byte == b'_' || byte == b'.'
+}
+
+/// Escape a string for use in a unit name, as `systemd-escape` does:
+/// `/` becomes `-`, and `-`, `\`, a leading `.` and anything outside
+/// `[a-zA-Z0-9:_.]` become `\xNN`, byte by byte.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (idx, &byte) in value.as_bytes().iter().enumerate() {
+        if byte == b'/' {
+            escaped.push('-');
+        } else if (idx == 0 && byte == b'.') || !is_valid_char(byte) {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        } else {
+            escaped.push(byte as char);
+        }
+    }
+    escaped
+}
+
+/// Reverse `escape`. Returns None for a malformed `\x` escape or if
+/// the result isn't valid UTF-8.
+pub fn unescape(escaped: &str) -> Option<String> {
+    let bytes = escaped.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'-' => {
+                unescaped.push(b'/');
+                idx += 1;
+            }
+            b'\\' => {
+                if bytes.get(idx + 1) != Some(&b'x') {
+                    return None;
+                }
+                let hex = escaped.get(idx + 2..idx + 4)?;
+                unescaped.push(u8::from_str_radix(hex, 16).ok()?);
+                idx += 4;
+            }
+            byte => {
+                unescaped.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8(unescaped).ok()
+}
+
+/// Escape a path, as `systemd-escape --path` does.
+///
+/// Duplicate slashes and `.` components are dropped, leading and
+/// trailing slashes are stripped, and the root directory is `-`.
+/// Returns None for paths containing `..`, which systemd refuses.
+pub fn escape_path(path: &str) -> Option<String> {
+    let mut components = vec![];
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return None,
+            component => components.push(component),
+        }
+    }
+
+    if components.is_empty() {
+        Some("-".to_string())
+    } else {
+        Some(escape(&components.join("/")))
+    }
+}
+
+/// Reverse `escape_path`, always producing an absolute path.
+pub fn unescape_path(escaped: &str) -> Option<String> {
+    if escaped == "-" {
+        return Some("/".to_string());
+    }
+    unescape(escaped).map(|path| format!("/{}", path))
+}
+
+/// The unit for a path with the given suffix, ie: `mount`.
+pub fn path_to_unit(path: &str, suffix: &str) -> Option<String> {
+    escape_path(path).map(|escaped| format!("{}.{}", escaped, suffix))
+}
+
+/// `/home/foo` is managed by `home-foo.mount`.
+pub fn path_to_mount_unit(path: &str) -> Option<String> {
+    path_to_unit(path, "mount")
+}
+
+/// `/dev/sda2` is managed by `dev-sda2.swap`.
+pub fn path_to_swap_unit(path: &str) -> Option<String> {
+    path_to_unit(path, "swap")
+}
+
+/// `/dev/sda2` is represented by `dev-sda2.device`.
+pub fn path_to_device_unit(path: &str) -> Option<String> {
+    path_to_unit(path, "device")
+}
+
+/// The path a unit name refers to, and the unit's type:
+/// `home-foo.mount` is `("/home/foo", "mount")`.
+pub fn unit_to_path(unit: &str) -> Option<(String, &str)> {
+    let idx = unit.rfind('.')?;
+    let path = unescape_path(&unit[..idx])?;
+    Some((path, &unit[idx + 1..]))
+}
+
+impl<'a> Source<'a> {
+    /// The `.device` unit a mount of this source waits for.
+    pub fn device_unit(&self) -> Option<String> {
+        path_to_device_unit(&self.device_path()?)
+    }
+}
+
+impl<'a> FSTabEntry<'a> {
+    /// The mount unit systemd-fstab-generator creates for this entry.
+    /// None for swap and for relative mount points.
+    pub fn mount_unit(&self) -> Option<String> {
+        if self.is_swap() || !self.file.starts_with('/') {
+            return None;
+        }
+        path_to_mount_unit(&self.file)
+    }
+
+    /// The swap unit systemd-fstab-generator creates for this entry,
+    /// named after the device path so `UUID=...` becomes
+    /// `dev-disk-by\x2duuid-....swap`. None if this isn't swap.
+    pub fn swap_unit(&self) -> Option<String> {
+        if !self.is_swap() {
+            return None;
+        }
+        let source = self.source();
+        match source.device_path() {
+            Some(path) => path_to_swap_unit(&path),
+            None => match source {
+                Source::Path(path) => path_to_swap_unit(path),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fstab::parse_fstab_line;
+
+    #[test]
+    fn mount_units() {
+        // Each expectation matches `systemd-escape --path --suffix=mount`.
+        for &(path, unit) in &[
+            ("/", "-.mount"),
+            ("//", "-.mount"),
+            ("/home", "home.mount"),
+            ("/home/foo", "home-foo.mount"),
+            ("/home/foo/", "home-foo.mount"),
+            ("//home//foo", "home-foo.mount"),
+            ("/home/./foo", "home-foo.mount"),
+            ("/any/foo/", "any-foo.mount"),
+            ("/mnt/my-disk", "mnt-my\\x2ddisk.mount"),
+            ("/mnt/My Disk", "mnt-My\\x20Disk.mount"),
+            ("/mnt/a\\b", "mnt-a\\x5cb.mount"),
+            ("/mnt/a:b_c.d", "mnt-a:b_c.d.mount"),
+            ("/.hidden", "\\x2ehidden.mount"),
+            ("/mnt/.hidden", "mnt-.hidden.mount"),
+            ("/mnt/ü", "mnt-\\xc3\\xbc.mount"),
+            ("/mnt/@home", "mnt-\\x40home.mount"),
+        ] {
+            assert_eq!(path_to_mount_unit(path).as_deref(), Some(unit), "{}", path);
+        }
+    }
+
+    #[test]
+    fn parent_directory_is_refused() {
+        assert_eq!(path_to_mount_unit("/mnt/../etc"), None);
+    }
+
+    #[test]
+    fn swap_and_device_units() {
+        assert_eq!(
+            path_to_swap_unit("/dev/disk/by-uuid/102799bd-d9d2-4ef6-936f-6ba9b59f168e").unwrap(),
+            "dev-disk-by\\x2duuid-102799bd\\x2dd9d2\\x2d4ef6\\x2d936f\\x2d6ba9b59f168e.swap"
+        );
+        assert_eq!(path_to_device_unit("/dev/sda1").unwrap(), "dev-sda1.device");
+        assert_eq!(
+            Source::parse("LABEL=my disk").device_unit().unwrap(),
+            "dev-disk-by\\x2dlabel-my\\x5cx20disk.device"
+        );
+        assert_eq!(Source::parse("tmpfs").device_unit(), None);
+    }
+
+    #[test]
+    fn unescape_round_trips() {
+        for path in &["/", "/home/foo", "/mnt/my-disk", "/mnt/My Disk", "/.hidden", "/mnt/ü", "/mnt/a\\b"] {
+            let unit = path_to_mount_unit(path).unwrap();
+            assert_eq!(unit_to_path(&unit), Some((path.to_string(), "mount")));
+        }
+    }
+
+    #[test]
+    fn unescape_malformed() {
+        assert_eq!(unescape("a\\x2"), None);
+        assert_eq!(unescape("a\\y20"), None);
+        assert_eq!(unescape("a\\xzz"), None);
+        assert_eq!(unescape("\\xff"), None);
+        assert_eq!(unit_to_path("no-suffix"), None);
+    }
+
+    #[test]
+    fn entry_units() {
+        let root = parse_fstab_line("/dev/sda1 / ext4 defaults 0 1").unwrap();
+        assert_eq!(root.mount_unit().unwrap(), "-.mount");
+        assert_eq!(root.swap_unit(), None);
+
+        let spaced = parse_fstab_line("/dev/sda1 /mnt/My\\040Disk ext4").unwrap();
+        assert_eq!(spaced.mount_unit().unwrap(), "mnt-My\\x20Disk.mount");
+
+        let uuid_swap = parse_fstab_line("UUID=1f2aa318-9c34 swap swap defaults 0 0").unwrap();
+        assert_eq!(uuid_swap.mount_unit(), None);
+        assert_eq!(
+            uuid_swap.swap_unit().unwrap(),
+            "dev-disk-by\\x2duuid-1f2aa318\\x2d9c34.swap"
+        );
+
+        let swapfile = parse_fstab_line("/swapfile none swap").unwrap();
+        assert_eq!(swapfile.swap_unit().unwrap(), "swapfile.swap");
+    }
+}